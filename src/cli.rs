@@ -13,12 +13,23 @@ pub enum OutputRequest {
     X,
     R,
     Mcs,
+    Sys,
+    Z,
+    Prg,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ScdMode {
+    Strict,
+    Lenient,
+    Strip,
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum RelocationCheckMode {
     Strict,
     Skip,
+    SelfRelocate,
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
@@ -34,6 +45,22 @@ pub enum SymbolMode {
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SizeReportMode {
+    Text,
+    Json,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CpuModel {
+    M68000,
+    M68020,
+    M68030,
+    M68040,
+    M68060,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[allow(clippy::struct_excessive_bools)]
 pub struct RuntimeConfig {
     pub g2lk_mode: G2lkMode,
     pub output_request: OutputRequest,
@@ -42,6 +69,7 @@ pub struct RuntimeConfig {
     pub symbol_mode: SymbolMode,
     pub section_info: bool,
     pub verbose: bool,
+    pub warn_off: bool,
     pub title: bool,
     pub base_address: u32,
     pub load_mode: u8,
@@ -50,9 +78,87 @@ pub struct RuntimeConfig {
 #[derive(Debug, Clone)]
 pub struct DefineArg {
     pub name: String,
-    pub value: u32,
+    pub expr: DefineExpr,
+}
+
+/// `-d` right-hand side: a sum of numbers and symbol references.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DefineExpr {
+    pub terms: Vec<DefineTerm>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DefineTerm {
+    pub negate: bool,
+    pub operand: DefineOperand,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DefineOperand {
+    Number(u32),
+    Symbol(String),
+}
+
+impl DefineExpr {
+    #[must_use]
+    pub fn number(value: u32) -> Self {
+        Self {
+            terms: vec![DefineTerm {
+                negate: false,
+                operand: DefineOperand::Number(value),
+            }],
+        }
+    }
+
+    /// Returns the value when the expression has no symbol references.
+    #[must_use]
+    pub fn constant(&self) -> Option<u32> {
+        self.terms.iter().try_fold(0u32, |acc, term| match term.operand {
+            DefineOperand::Number(v) if term.negate => Some(acc.wrapping_sub(v)),
+            DefineOperand::Number(v) => Some(acc.wrapping_add(v)),
+            DefineOperand::Symbol(_) => None,
+        })
+    }
+
+    pub fn symbols(&self) -> impl Iterator<Item = &str> {
+        self.terms.iter().filter_map(|term| match &term.operand {
+            DefineOperand::Symbol(name) => Some(name.as_str()),
+            DefineOperand::Number(_) => None,
+        })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct JustSymbolsArg {
+    pub path: PathBuf,
+    pub load_base: Option<u32>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum EntryArg {
+    Symbol(String),
+    /// Absolute address; the base address (`-b`, else 0) is subtracted.
+    Address(u32),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct OverlayArg {
+    pub output: PathBuf,
+    pub inputs: Vec<PathBuf>,
 }
 
+/// `--max-size SECTION=SIZE`; `section` is one of `MAX_SIZE_SECTIONS`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MaxSizeArg {
+    pub section: String,
+    pub limit: u32,
+}
+
+pub(crate) const MAX_SIZE_SECTIONS: [&str; 14] = [
+    "text", "data", "bss", "common", "stack", "rdata", "rbss", "rcommon", "rstack", "rldata",
+    "rlbss", "rlcommon", "rlstack", "total",
+];
+
 fn parse_u32_with_hex(input: &str) -> Result<u32, String> {
     let s = input.trim();
     if let Some(hex) = s.strip_prefix("0x").or_else(|| s.strip_prefix("0X")) {
@@ -71,6 +177,90 @@ fn parse_load_mode(input: &str) -> Result<u8, String> {
     u8::try_from(v).map_err(|_| format!("load mode must be 0..2: {input}"))
 }
 
+fn parse_size_report_mode(input: &str) -> Result<SizeReportMode, String> {
+    match input.trim() {
+        "text" => Ok(SizeReportMode::Text),
+        "json" => Ok(SizeReportMode::Json),
+        _ => Err(format!("size report format must be text or json: {input}")),
+    }
+}
+
+fn parse_scd_mode(input: &str) -> Result<ScdMode, String> {
+    match input.trim() {
+        "strict" => Ok(ScdMode::Strict),
+        "lenient" => Ok(ScdMode::Lenient),
+        "strip" => Ok(ScdMode::Strip),
+        _ => Err(format!("scd mode must be strict, lenient or strip: {input}")),
+    }
+}
+
+fn parse_just_symbols_arg(input: &str) -> Result<JustSymbolsArg, String> {
+    match input.rsplit_once('@') {
+        Some((path, base)) if !path.is_empty() => Ok(JustSymbolsArg {
+            path: PathBuf::from(path),
+            load_base: Some(parse_u32_with_hex(base)?),
+        }),
+        _ => Ok(JustSymbolsArg {
+            path: PathBuf::from(input),
+            load_base: None,
+        }),
+    }
+}
+
+fn parse_max_size_arg(input: &str) -> Result<MaxSizeArg, String> {
+    let (name, limit) = input
+        .split_once('=')
+        .ok_or_else(|| format!("max size format must be SECTION=SIZE: {input}"))?;
+    let limit = parse_u32_with_hex(limit)?;
+    let section = name.trim();
+    if !MAX_SIZE_SECTIONS.contains(&section) {
+        return Err(format!("unknown section in --max-size (text, data, bss, ..., total): {input}"));
+    }
+    Ok(MaxSizeArg {
+        section: section.to_string(),
+        limit,
+    })
+}
+
+fn parse_entry_arg(input: &str) -> Result<EntryArg, String> {
+    let s = input.trim();
+    match s.chars().next() {
+        None => Err("entry symbol is empty".to_string()),
+        Some(c) if c.is_ascii_digit() => parse_u32_with_hex(s).map(EntryArg::Address),
+        Some(_) => Ok(EntryArg::Symbol(s.to_string())),
+    }
+}
+
+fn parse_cpu(input: &str) -> Result<CpuModel, String> {
+    match input.trim().trim_start_matches("mc") {
+        "68000" => Ok(CpuModel::M68000),
+        "68020" => Ok(CpuModel::M68020),
+        "68030" => Ok(CpuModel::M68030),
+        "68040" => Ok(CpuModel::M68040),
+        "68060" => Ok(CpuModel::M68060),
+        _ => Err(format!("cpu must be 68000, 68020, 68030, 68040 or 68060: {input}")),
+    }
+}
+
+fn parse_overlay_arg(input: &str) -> Result<OverlayArg, String> {
+    let (output, inputs) = input
+        .split_once('=')
+        .ok_or_else(|| format!("overlay format must be NAME=INPUT[,INPUT...]: {input}"))?;
+    let inputs = inputs
+        .split(',')
+        .map(str::trim)
+        .filter(|v| !v.is_empty())
+        .map(PathBuf::from)
+        .collect::<Vec<_>>();
+    if output.trim().is_empty() || inputs.is_empty() {
+        return Err(format!("overlay format must be NAME=INPUT[,INPUT...]: {input}"));
+    }
+    Ok(OverlayArg {
+        output: PathBuf::from(output.trim()),
+        inputs,
+    })
+}
+
 fn parse_define_arg(input: &str) -> Result<DefineArg, String> {
     let (name_raw, value_raw) = input
         .split_once('=')
@@ -79,13 +269,56 @@ fn parse_define_arg(input: &str) -> Result<DefineArg, String> {
     if name.is_empty() {
         return Err(format!("define name is empty: {input}"));
     }
-    let value = parse_u32_with_hex(value_raw.trim())?;
+    let expr = parse_define_expr(value_raw)?;
     Ok(DefineArg {
         name: name.to_string(),
-        value,
+        expr,
     })
 }
 
+fn parse_define_expr(input: &str) -> Result<DefineExpr, String> {
+    let mut terms = Vec::new();
+    let mut rest = input.trim_start();
+    let mut negate = false;
+    if let Some(r) = rest.strip_prefix('-') {
+        negate = true;
+        rest = r.trim_start();
+    } else if let Some(r) = rest.strip_prefix('+') {
+        rest = r.trim_start();
+    }
+    loop {
+        let end = rest
+            .find(|c: char| c == '+' || c == '-' || c.is_whitespace())
+            .unwrap_or(rest.len());
+        let token = &rest[..end];
+        if token.is_empty() {
+            return Err(format!("invalid define expression: {input}"));
+        }
+        let operand = if token.starts_with(|c: char| c.is_ascii_digit() || c == '$') {
+            let value = match token.strip_prefix('$') {
+                Some(hex) => u32::from_str_radix(hex, 16)
+                    .map_err(|e| format!("invalid hex value '{token}': {e}"))?,
+                None => parse_u32_with_hex(token)?,
+            };
+            DefineOperand::Number(value)
+        } else {
+            DefineOperand::Symbol(token.to_string())
+        };
+        terms.push(DefineTerm { negate, operand });
+        rest = rest[end..].trim_start();
+        if rest.is_empty() {
+            break;
+        }
+        negate = match rest.as_bytes()[0] {
+            b'+' => false,
+            b'-' => true,
+            _ => return Err(format!("invalid define expression: {input}")),
+        };
+        rest = rest[1..].trim_start();
+    }
+    Ok(DefineExpr { terms })
+}
+
 fn normalize_argv_from_iter<I>(args: I) -> Vec<OsString>
 where
     I: IntoIterator<Item = OsString>,
@@ -160,6 +393,9 @@ pub struct Args {
     #[arg(long = "rn")]
     pub r_no_check: bool,
 
+    #[arg(long = "self-relocate")]
+    pub self_relocate: bool,
+
     #[arg(short = 'a')]
     pub no_x_ext: bool,
 
@@ -175,7 +411,7 @@ pub struct Args {
     #[arg(short = 'g', value_parser = parse_load_mode)]
     pub load_mode: Option<u8>,
 
-    #[arg(short = 'd', value_parser = parse_define_arg)]
+    #[arg(short = 'd', long = "defsym", value_parser = parse_define_arg)]
     pub defines: Vec<DefineArg>,
 
     #[arg(short = 'i')]
@@ -199,6 +435,15 @@ pub struct Args {
     #[arg(long = "makemcs")]
     pub make_mcs: bool,
 
+    #[arg(long = "device")]
+    pub make_device: bool,
+
+    #[arg(long = "z-format")]
+    pub z_format: bool,
+
+    #[arg(long = "prg")]
+    pub make_prg: bool,
+
     #[arg(long = "omit-bss")]
     pub omit_bss: bool,
 
@@ -208,6 +453,9 @@ pub struct Args {
     #[arg(short = 'p', long = "map", num_args = 0..=1, default_missing_value = "")]
     pub map: Option<String>,
 
+    #[arg(long = "debug-file", value_name = "FILE", num_args = 0..=1, default_missing_value = "")]
+    pub debug_file: Option<String>,
+
     #[arg(long = "verbose", short = 'v')]
     pub verbose: bool,
 
@@ -223,6 +471,80 @@ pub struct Args {
     #[arg(short = 's')]
     pub section_info: bool,
 
+    #[arg(long = "size-report", num_args = 0..=1, default_missing_value = "")]
+    pub size_report: Option<String>,
+
+    #[arg(long = "size-format", value_parser = parse_size_report_mode, default_value = "text")]
+    pub size_format: SizeReportMode,
+
+    #[arg(long = "size-diff", num_args = 2, value_names = ["OLD", "NEW"])]
+    pub size_diff: Vec<PathBuf>,
+
+    #[arg(long = "why-extract", value_name = "FILE")]
+    pub why_extract: Option<PathBuf>,
+
+    #[arg(long = "trace-symbol", value_name = "NAME")]
+    pub trace_symbols: Vec<String>,
+
+    #[arg(long = "allow-multiple-definition")]
+    pub allow_multiple_definition: bool,
+
+    #[arg(long = "wrap", value_name = "SYMBOL")]
+    pub wraps: Vec<String>,
+
+    #[arg(long = "symbol-file", value_name = "FILE")]
+    pub symbol_files: Vec<PathBuf>,
+
+    #[arg(long = "just-symbols", value_name = "FILE[@BASE]", value_parser = parse_just_symbols_arg)]
+    pub just_symbols: Vec<JustSymbolsArg>,
+
+    #[arg(
+        long = "max-size",
+        value_name = "SECTION=SIZE[,...]",
+        value_delimiter = ',',
+        value_parser = parse_max_size_arg
+    )]
+    pub max_sizes: Vec<MaxSizeArg>,
+
+    #[arg(long = "print-memory-usage")]
+    pub print_memory_usage: bool,
+
+    #[arg(long = "stack-size", value_name = "N", value_parser = parse_u32_with_hex)]
+    pub stack_size: Option<u32>,
+
+    /// Entry point: a symbol or an absolute address. With -b the address
+    /// includes the base, so `-b 0x10000 --entry 0x10000` starts at text.
+    #[arg(long = "entry", value_name = "NAME|ADDRESS", value_parser = parse_entry_arg)]
+    pub entry: Option<EntryArg>,
+
+    #[arg(long = "cpu", value_name = "MODEL", value_parser = parse_cpu)]
+    pub cpu: Option<CpuModel>,
+
+    #[arg(long = "target-check")]
+    pub target_check: bool,
+
+    #[arg(long = "branch-islands")]
+    pub branch_islands: bool,
+
+    #[arg(long = "scd", value_name = "MODE", value_parser = parse_scd_mode, default_value = "lenient")]
+    pub scd: ScdMode,
+
+    /// Alias of --scd=strip.
+    #[arg(long = "strip-debug")]
+    pub strip_debug: bool,
+
+    #[arg(long = "strip-all")]
+    pub strip_all: bool,
+
+    #[arg(long = "keep-symbol", value_name = "SYMBOL")]
+    pub keep_symbols: Vec<String>,
+
+    #[arg(long = "strip-symbol", value_name = "SYMBOL")]
+    pub strip_symbols: Vec<String>,
+
+    #[arg(long = "overlay", value_name = "NAME=INPUT[,INPUT...]", value_parser = parse_overlay_arg)]
+    pub overlays: Vec<OverlayArg>,
+
     #[arg(value_name = "INPUT")]
     pub inputs: Vec<PathBuf>,
 }
@@ -237,9 +559,25 @@ impl Args {
         }
     }
 
+    /// `--strip-debug` and `--strip-all` override `--scd`.
+    #[must_use]
+    pub fn scd_mode(&self) -> ScdMode {
+        if self.strip_debug || self.strip_all {
+            ScdMode::Strip
+        } else {
+            self.scd
+        }
+    }
+
     #[must_use]
     pub fn output_request(&self) -> OutputRequest {
-        if self.make_mcs {
+        if self.make_prg {
+            OutputRequest::Prg
+        } else if self.z_format {
+            OutputRequest::Z
+        } else if self.make_device {
+            OutputRequest::Sys
+        } else if self.make_mcs {
             OutputRequest::Mcs
         } else if self.r_format {
             OutputRequest::R
@@ -253,7 +591,9 @@ impl Args {
         RuntimeConfig {
             g2lk_mode: self.g2lk_mode(),
             output_request: self.output_request(),
-            relocation_check: if self.r_no_check {
+            relocation_check: if self.self_relocate {
+                RelocationCheckMode::SelfRelocate
+            } else if self.r_no_check {
                 RelocationCheckMode::Skip
             } else {
                 RelocationCheckMode::Strict
@@ -263,13 +603,14 @@ impl Args {
             } else {
                 BssMode::Include
             },
-            symbol_mode: if self.cut_symbols {
+            symbol_mode: if self.cut_symbols || self.strip_all {
                 SymbolMode::Cut
             } else {
                 SymbolMode::Keep
             },
             section_info: self.section_info,
             verbose: self.verbose,
+            warn_off: self.warn_off,
             title: self.title,
             base_address: self.base_address.unwrap_or(0),
             load_mode: self.load_mode.unwrap_or(0),
@@ -279,9 +620,14 @@ impl Args {
 
 #[cfg(test)]
 mod tests {
-    use super::{Args, finalize_compat_args, normalize_argv_from_iter};
+    use super::{
+        Args, CpuModel, DefineExpr, DefineOperand, EntryArg, MaxSizeArg, finalize_compat_args,
+        normalize_argv_from_iter, parse_cpu, parse_define_arg, parse_entry_arg, parse_overlay_arg, parse_scd_mode, ScdMode,
+        SymbolMode,
+    };
     use clap::Parser;
     use std::ffi::OsString;
+    use std::path::PathBuf;
 
     #[test]
     fn finalizes_verbose_by_last_switch() {
@@ -316,6 +662,93 @@ mod tests {
         assert!(err.contains("mutually exclusive"));
     }
 
+    #[test]
+    fn parses_define_constant_and_symbol_expressions() {
+        let def = parse_define_arg("_FOO=0x1234").expect("constant");
+        assert_eq!(def.expr, DefineExpr::number(0x1234));
+        assert_eq!(def.expr.constant(), Some(0x1234));
+
+        let def = parse_define_arg("_heap = _end + $100 - 4").expect("expr");
+        assert_eq!(def.name, "_heap");
+        assert_eq!(def.expr.constant(), None);
+        assert_eq!(def.expr.symbols().collect::<Vec<_>>(), vec!["_end"]);
+        assert_eq!(def.expr.terms[1].operand, DefineOperand::Number(0x100));
+        assert!(def.expr.terms[2].negate);
+
+        assert!(parse_define_arg("alias=_main+").is_err());
+        assert!(parse_define_arg("alias=_main _end").is_err());
+    }
+
+    #[test]
+    fn parses_overlay_groups() {
+        let overlay = parse_overlay_arg("stage1.r=a.o, b.o,libc.a").expect("overlay");
+        assert_eq!(overlay.output, PathBuf::from("stage1.r"));
+        assert_eq!(
+            overlay.inputs,
+            vec![PathBuf::from("a.o"), PathBuf::from("b.o"), PathBuf::from("libc.a")]
+        );
+        assert!(parse_overlay_arg("stage1.r=").is_err());
+        assert!(parse_overlay_arg("a.o,b.o").is_err());
+    }
+
+    #[test]
+    fn parses_max_size_budgets() {
+        let args = Args::parse_from(["rhlk", "--max-size", "text=0x8000,total=65536", "a.o"]);
+        assert_eq!(
+            args.max_sizes,
+            vec![
+                MaxSizeArg {
+                    section: "text".to_string(),
+                    limit: 0x8000,
+                },
+                MaxSizeArg {
+                    section: "total".to_string(),
+                    limit: 65536,
+                },
+            ]
+        );
+        assert!(Args::try_parse_from(["rhlk", "--max-size", "heap=4", "a.o"]).is_err());
+    }
+
+    #[test]
+    fn parses_entry_symbol_or_address() {
+        assert_eq!(parse_entry_arg("_start"), Ok(EntryArg::Symbol("_start".to_string())));
+        assert_eq!(parse_entry_arg("0x100"), Ok(EntryArg::Address(0x100)));
+        assert_eq!(parse_entry_arg("16"), Ok(EntryArg::Address(16)));
+        assert!(parse_entry_arg("0xzz").is_err());
+        assert!(parse_entry_arg("").is_err());
+    }
+
+    #[test]
+    fn parses_cpu_model() {
+        assert_eq!(parse_cpu("68000"), Ok(CpuModel::M68000));
+        assert_eq!(parse_cpu("mc68030"), Ok(CpuModel::M68030));
+        assert_eq!(parse_cpu("mc68060"), Ok(CpuModel::M68060));
+        assert!(parse_cpu("68010").is_err());
+        assert_eq!(Args::parse_from(["rhlk", "a.o"]).cpu, None);
+        assert_eq!(Args::parse_from(["rhlk", "--cpu", "68040", "a.o"]).cpu, Some(CpuModel::M68040));
+    }
+
+    #[test]
+    fn parses_scd_mode() {
+        assert_eq!(Args::parse_from(["rhlk", "a.o"]).scd, ScdMode::Lenient);
+        assert_eq!(Args::parse_from(["rhlk", "--scd=strict", "a.o"]).scd, ScdMode::Strict);
+        assert_eq!(parse_scd_mode("strip"), Ok(ScdMode::Strip));
+        assert!(parse_scd_mode("keep").is_err());
+    }
+
+    #[test]
+    fn strip_all_cuts_symbols_and_debug_info() {
+        let args = Args::parse_from(["rhlk", "--scd=strict", "--strip-debug", "a.o"]);
+        assert_eq!(args.scd_mode(), ScdMode::Strip);
+        assert_eq!(args.runtime_config().symbol_mode, SymbolMode::Keep);
+
+        let args = Args::parse_from(["rhlk", "--strip-all", "--keep-symbol", "_main", "a.o"]);
+        assert_eq!(args.scd_mode(), ScdMode::Strip);
+        assert_eq!(args.runtime_config().symbol_mode, SymbolMode::Cut);
+        assert_eq!(args.keep_symbols, vec!["_main".to_string()]);
+    }
+
     #[test]
     fn normalizes_short_l_attached_form() {
         let argv = vec![
@@ -1,3 +1,5 @@
+use std::fmt;
+
 use thiserror::Error;
 
 #[derive(Debug, Error)]
@@ -10,6 +12,47 @@ pub enum FormatError {
     UnterminatedString,
 }
 
+/// The last command parsed before a `ParseError`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PreviousCommand {
+    pub code: u16,
+    pub offset: usize,
+}
+
+/// A `FormatError` located in the object stream.
+#[derive(Debug, Error)]
+pub struct ParseError {
+    pub kind: FormatError,
+    /// Byte offset of the command that failed to parse.
+    pub offset: usize,
+    /// Index of that command in the stream.
+    pub command_index: usize,
+    pub previous: Option<PreviousCommand>,
+    /// Bytes from `offset` on, for spotting dialect differences.
+    pub snippet: Vec<u8>,
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} at offset {:#x} (command #{}, ",
+            self.kind, self.offset, self.command_index
+        )?;
+        match self.previous {
+            Some(prev) => write!(f, "after {:#06x} at {:#x})", prev.code, prev.offset)?,
+            None => write!(f, "first command)")?,
+        }
+        if !self.snippet.is_empty() {
+            write!(f, ": bytes")?;
+            for byte in &self.snippet {
+                write!(f, " {byte:02x}")?;
+            }
+        }
+        Ok(())
+    }
+}
+
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum Section {
     Abs,
@@ -22,3 +65,5 @@ pub enum Section {
 }
 
 pub mod obj;
+pub mod symfile;
+pub mod xfile;
@@ -1,4 +1,7 @@
-use crate::format::FormatError;
+use crate::format::{FormatError, ParseError, PreviousCommand};
+
+/// Bytes of the failing command shown in a `ParseError`.
+const SNIPPET_LEN: usize = 16;
 
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub struct ObjectFile {
@@ -44,85 +47,36 @@ pub enum Command {
 /// # Errors
 /// Returns `FormatError` when the stream is malformed or contains unsupported commands.
 pub fn parse_object(input: &[u8]) -> Result<ObjectFile, FormatError> {
+    parse_object_at(input).map_err(|e| e.kind)
+}
+
+/// Like [`parse_object`], but reports where in the stream parsing failed.
+///
+/// # Errors
+/// Returns `ParseError` with the failing command's offset, index, the command
+/// before it and the bytes at that offset.
+pub fn parse_object_at(input: &[u8]) -> Result<ObjectFile, ParseError> {
     let mut reader = Reader::new(input);
     let mut commands = Vec::new();
+    let mut previous = None;
 
     while !reader.is_eof() {
-        let code = reader.read_u16_be()?;
-        match code {
-            0x0000 => {
-                commands.push(Command::End);
-                break;
-            }
-            0x3000 => {
-                let size = reader.read_u32_be()?;
-                commands.push(Command::DefineSpace { size });
-            }
-            0xd000 => {
-                let size = reader.read_u32_be()?;
-                let name = reader.read_cstring_even()?;
-                commands.push(Command::SourceFile { size, name });
-            }
-            0xe000 => {
-                let section = reader.read_u16_be()?;
-                let address = reader.read_u32_be()?;
-                commands.push(Command::StartAddress { section, address });
-            }
-            0xe001 => {
-                let file_name = reader.read_cstring_even()?;
-                commands.push(Command::Request { file_name });
-            }
-            0xe00c | 0xe00d => {
-                commands.push(Command::Opaque {
-                    code,
-                    payload: Vec::new(),
-                });
-            }
-            _ if (code & 0xff00) == 0x1000 => {
-                let size = usize::from(code.to_be_bytes()[1]) + 1;
-                let data = reader.read_bytes(size)?.to_vec();
-                reader.align_even();
-                commands.push(Command::RawData(data));
-            }
-            _ if (code & 0xff00) == 0x2000 => {
-                let section = code.to_be_bytes()[1];
-                let _reserved = reader.read_u32_be()?;
-                commands.push(Command::ChangeSection { section });
-            }
-            _ if (code & 0xff00) == 0xc000 => {
-                let section = code.to_be_bytes()[1];
-                let size = reader.read_u32_be()?;
-                let name = reader.read_cstring_even()?;
-                commands.push(Command::Header {
-                    section,
-                    size,
-                    name,
-                });
-            }
-            _ if (code & 0xff00) == 0xb200 => {
-                let section = code.to_be_bytes()[1];
-                let value = reader.read_u32_be()?;
-                let name = reader.read_cstring_even()?;
-                commands.push(Command::DefineSymbol {
-                    section,
-                    value,
-                    name,
-                });
-            }
-            0xb0ff => {
-                let value = reader.read_u32_be()?;
-                let name = reader.read_cstring_even()?;
-                commands.push(Command::DefineSymbol {
-                    section: 0xff,
-                    value,
-                    name,
-                });
-            }
-            _ if is_supported_opaque(code) => {
-                let payload = read_opaque_payload(&mut reader, code)?;
-                commands.push(Command::Opaque { code, payload });
-            }
-            _ => return Err(FormatError::UnsupportedCommand(code)),
+        let offset = reader.pos;
+        let command = reader
+            .read_u16_be()
+            .and_then(|code| read_command(&mut reader, code).map(|cmd| (code, cmd)));
+        let (code, command) = command.map_err(|kind| ParseError {
+            kind,
+            offset,
+            command_index: commands.len(),
+            previous,
+            snippet: input[offset..input.len().min(offset + SNIPPET_LEN)].to_vec(),
+        })?;
+        let end = matches!(command, Command::End);
+        commands.push(command);
+        previous = Some(PreviousCommand { code, offset });
+        if end {
+            break;
         }
     }
 
@@ -130,6 +84,80 @@ pub fn parse_object(input: &[u8]) -> Result<ObjectFile, FormatError> {
     Ok(ObjectFile { commands, scd_tail })
 }
 
+fn read_command(reader: &mut Reader<'_>, code: u16) -> Result<Command, FormatError> {
+    let command = match code {
+        0x0000 => Command::End,
+        0x3000 => {
+            let size = reader.read_u32_be()?;
+            Command::DefineSpace { size }
+        }
+        0xd000 => {
+            let size = reader.read_u32_be()?;
+            let name = reader.read_cstring_even()?;
+            Command::SourceFile { size, name }
+        }
+        0xe000 => {
+            let section = reader.read_u16_be()?;
+            let address = reader.read_u32_be()?;
+            Command::StartAddress { section, address }
+        }
+        0xe001 => {
+            let file_name = reader.read_cstring_even()?;
+            Command::Request { file_name }
+        }
+        0xe00c | 0xe00d => Command::Opaque {
+            code,
+            payload: Vec::new(),
+        },
+        _ if (code & 0xff00) == 0x1000 => {
+            let size = usize::from(code.to_be_bytes()[1]) + 1;
+            let data = reader.read_bytes(size)?.to_vec();
+            reader.align_even();
+            Command::RawData(data)
+        }
+        _ if (code & 0xff00) == 0x2000 => {
+            let section = code.to_be_bytes()[1];
+            let _reserved = reader.read_u32_be()?;
+            Command::ChangeSection { section }
+        }
+        _ if (code & 0xff00) == 0xc000 => {
+            let section = code.to_be_bytes()[1];
+            let size = reader.read_u32_be()?;
+            let name = reader.read_cstring_even()?;
+            Command::Header {
+                section,
+                size,
+                name,
+            }
+        }
+        _ if (code & 0xff00) == 0xb200 => {
+            let section = code.to_be_bytes()[1];
+            let value = reader.read_u32_be()?;
+            let name = reader.read_cstring_even()?;
+            Command::DefineSymbol {
+                section,
+                value,
+                name,
+            }
+        }
+        0xb0ff => {
+            let value = reader.read_u32_be()?;
+            let name = reader.read_cstring_even()?;
+            Command::DefineSymbol {
+                section: 0xff,
+                value,
+                name,
+            }
+        }
+        _ if is_supported_opaque(code) => {
+            let payload = read_opaque_payload(reader, code)?;
+            Command::Opaque { code, payload }
+        }
+        _ => return Err(FormatError::UnsupportedCommand(code)),
+    };
+    Ok(command)
+}
+
 fn is_supported_opaque(code: u16) -> bool {
     let [hi, lo] = code.to_be_bytes();
     match hi {
@@ -219,7 +247,8 @@ impl<'a> Reader<'a> {
 
 #[cfg(test)]
 mod tests {
-    use super::{parse_object, Command};
+    use super::{parse_object, parse_object_at, Command};
+    use crate::format::{FormatError, PreviousCommand};
 
     #[test]
     fn parses_minimal_supported_stream() {
@@ -301,4 +330,30 @@ mod tests {
         );
         assert!(matches!(object.commands[2], Command::End));
     }
+
+    #[test]
+    fn locates_parse_errors_in_the_stream() {
+        let data: &[u8] = &[
+            // 10 01 data(2)
+            0x10, 0x01, 0xaa, 0xbb,
+            // unknown command
+            0x12, 0x34, 0x00, 0x00,
+        ];
+        let err = parse_object_at(data).expect_err("unknown command");
+        assert!(matches!(err.kind, FormatError::UnsupportedCommand(0x1234)));
+        assert_eq!(err.offset, 4);
+        assert_eq!(err.command_index, 1);
+        assert_eq!(err.previous, Some(PreviousCommand { code: 0x1001, offset: 0 }));
+        assert_eq!(
+            err.to_string(),
+            "unsupported object command: 0x1234 at offset 0x4 (command #1, after 0x1001 at 0x0): bytes 12 34 00 00"
+        );
+
+        // 20 01 with a truncated reserved long
+        let err = parse_object_at(&[0x20, 0x01, 0x00]).expect_err("truncated");
+        assert_eq!(
+            err.to_string(),
+            "unexpected end of file while reading object stream at offset 0x0 (command #0, first command): bytes 20 01 00"
+        );
+    }
 }
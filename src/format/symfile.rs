@@ -0,0 +1,150 @@
+use thiserror::Error;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SymbolDefinition {
+    pub name: Vec<u8>,
+    pub value: u32,
+    pub line: usize,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Error)]
+pub enum SymbolFileError {
+    #[error("line {line}: expected NAME=VALUE or NAME equ VALUE")]
+    InvalidDefinition { line: usize },
+    #[error("line {line}: invalid value")]
+    InvalidValue { line: usize },
+}
+
+/// Parses a symbol definition file (`NAME=VALUE` / `NAME equ VALUE` per line).
+///
+/// Values accept `$hex`, `0xhex` and decimal with an optional sign. `;` starts a
+/// comment, as does `*` in the first column. The text is handled as raw bytes so
+/// SJIS comments and names pass through untouched.
+///
+/// # Errors
+/// Returns `SymbolFileError` with the 1-based line number of the first malformed line.
+pub fn parse_symbol_file(input: &[u8]) -> Result<Vec<SymbolDefinition>, SymbolFileError> {
+    let mut out = Vec::new();
+    for (idx, raw) in input.split(|&b| b == b'\n').enumerate() {
+        let line = idx + 1;
+        // 0x3b never appears as a Shift_JIS trail byte, so a byte split is safe.
+        let body = raw.split(|&b| b == b';').next().unwrap_or_default();
+        if body.first() == Some(&b'*') {
+            continue;
+        }
+        let body = body.trim_ascii();
+        if body.is_empty() {
+            continue;
+        }
+        let (name, value) =
+            split_definition(body).ok_or(SymbolFileError::InvalidDefinition { line })?;
+        let value = parse_value(value).ok_or(SymbolFileError::InvalidValue { line })?;
+        out.push(SymbolDefinition {
+            name: name.to_vec(),
+            value,
+            line,
+        });
+    }
+    Ok(out)
+}
+
+fn split_definition(body: &[u8]) -> Option<(&[u8], &[u8])> {
+    if let Some(pos) = body.iter().position(|&b| b == b'=') {
+        let name = body[..pos].trim_ascii();
+        let value = body[pos + 1..].trim_ascii();
+        return (is_name(name) && !value.is_empty()).then_some((name, value));
+    }
+    let name_end = body.iter().position(u8::is_ascii_whitespace)?;
+    let name = body[..name_end]
+        .strip_suffix(b":")
+        .unwrap_or(&body[..name_end]);
+    let rest = body[name_end..].trim_ascii_start();
+    let op_end = rest
+        .iter()
+        .position(u8::is_ascii_whitespace)
+        .unwrap_or(rest.len());
+    let op = &rest[..op_end];
+    if !op.eq_ignore_ascii_case(b"equ") && !op.eq_ignore_ascii_case(b".equ") {
+        return None;
+    }
+    let value = rest[op_end..].trim_ascii();
+    (is_name(name) && !value.is_empty()).then_some((name, value))
+}
+
+fn is_name(name: &[u8]) -> bool {
+    !name.is_empty()
+        && !name[0].is_ascii_digit()
+        && !name
+            .iter()
+            .any(|&b| b.is_ascii_whitespace() || matches!(b, b'=' | b'$' | b'+' | b'-'))
+}
+
+fn parse_value(text: &[u8]) -> Option<u32> {
+    let text = std::str::from_utf8(text).ok()?;
+    let (negate, digits) = match text.as_bytes().first() {
+        Some(b'-') => (true, &text[1..]),
+        Some(b'+') => (false, &text[1..]),
+        _ => (false, text),
+    };
+    let value = if let Some(hex) = digits.strip_prefix('$') {
+        u32::from_str_radix(hex, 16).ok()?
+    } else if let Some(hex) = digits
+        .strip_prefix("0x")
+        .or_else(|| digits.strip_prefix("0X"))
+    {
+        u32::from_str_radix(hex, 16).ok()?
+    } else {
+        digits.parse::<u32>().ok()?
+    };
+    Some(if negate { value.wrapping_neg() } else { value })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{parse_symbol_file, SymbolDefinition, SymbolFileError};
+
+    #[test]
+    fn parses_assignment_and_equ_forms() {
+        let mut src = b"; X68000 I/O\r\n".to_vec();
+        src.extend_from_slice(b"* comment line\n");
+        src.extend_from_slice(b"_CRTC_R00 equ $E80000 ; \x83\x65\x83\x4c\x83\x58\x83\x67\n");
+        src.extend_from_slice(b"_B_PRINT: EQU 0x21\n");
+        src.extend_from_slice(b"\n");
+        src.extend_from_slice(b"STACK_SIZE = 4096\n");
+        src.extend_from_slice(b"MINUS=-1\n");
+        let defs = parse_symbol_file(&src).expect("parse");
+        assert_eq!(
+            defs,
+            vec![
+                SymbolDefinition {
+                    name: b"_CRTC_R00".to_vec(),
+                    value: 0x00e8_0000,
+                    line: 3,
+                },
+                SymbolDefinition {
+                    name: b"_B_PRINT".to_vec(),
+                    value: 0x21,
+                    line: 4,
+                },
+                SymbolDefinition {
+                    name: b"STACK_SIZE".to_vec(),
+                    value: 4096,
+                    line: 6,
+                },
+                SymbolDefinition {
+                    name: b"MINUS".to_vec(),
+                    value: 0xffff_ffff,
+                    line: 7,
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn reports_line_of_malformed_definition() {
+        let err = parse_symbol_file(b"A=1\nB set 2\n").expect_err("must fail");
+        assert_eq!(err, SymbolFileError::InvalidDefinition { line: 2 });
+        let err = parse_symbol_file(b"A=1\nB=$zz\n").expect_err("must fail");
+        assert_eq!(err, SymbolFileError::InvalidValue { line: 2 });
+    }
+}
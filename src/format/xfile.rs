@@ -0,0 +1,124 @@
+use thiserror::Error;
+
+const X_HEADER_SIZE: usize = 64;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct XSymbol {
+    pub kind: u16,
+    pub value: u32,
+    pub name: Vec<u8>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct XSymbolTable {
+    pub base_address: u32,
+    pub symbols: Vec<XSymbol>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Error)]
+pub enum XFileError {
+    #[error("X形式実行ファイルではありません")]
+    NotExecutable,
+    #[error("unexpected end of file while reading X symbol table")]
+    UnexpectedEof,
+}
+
+/// Reads the base address and symbol table of a Human68k `.x` executable.
+///
+/// Entries use the layout written by the linker: `kind:u16`, `value:u32`,
+/// NUL-terminated name padded to an even length.
+///
+/// # Errors
+/// Returns `XFileError` when the header is not `HU` or the table is truncated.
+pub fn parse_x_symbols(input: &[u8]) -> Result<XSymbolTable, XFileError> {
+    if input.len() < X_HEADER_SIZE || &input[0..2] != b"HU" {
+        return Err(XFileError::NotExecutable);
+    }
+    let base_address = read_u32(input, 4);
+    let text_size = read_u32(input, 12) as usize;
+    let data_size = read_u32(input, 16) as usize;
+    let reloc_size = read_u32(input, 24) as usize;
+    let symbol_size = read_u32(input, 28) as usize;
+    let start = X_HEADER_SIZE
+        .checked_add(text_size)
+        .and_then(|v| v.checked_add(data_size))
+        .and_then(|v| v.checked_add(reloc_size))
+        .ok_or(XFileError::UnexpectedEof)?;
+    let end = start
+        .checked_add(symbol_size)
+        .ok_or(XFileError::UnexpectedEof)?;
+    let table = input.get(start..end).ok_or(XFileError::UnexpectedEof)?;
+
+    let mut symbols = Vec::new();
+    let mut pos = 0usize;
+    while pos < table.len() {
+        if table.len() - pos < 6 {
+            return Err(XFileError::UnexpectedEof);
+        }
+        let kind = u16::from_be_bytes([table[pos], table[pos + 1]]);
+        let value = read_u32(table, pos + 2);
+        pos += 6;
+        let name_len = table[pos..]
+            .iter()
+            .position(|&b| b == 0)
+            .ok_or(XFileError::UnexpectedEof)?;
+        let name = table[pos..pos + name_len].to_vec();
+        pos += name_len + 1;
+        if !pos.is_multiple_of(2) {
+            pos += 1;
+        }
+        symbols.push(XSymbol { kind, value, name });
+    }
+    Ok(XSymbolTable {
+        base_address,
+        symbols,
+    })
+}
+
+fn read_u32(bytes: &[u8], at: usize) -> u32 {
+    u32::from_be_bytes([bytes[at], bytes[at + 1], bytes[at + 2], bytes[at + 3]])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{parse_x_symbols, XFileError, XSymbol};
+
+    #[test]
+    fn reads_symbols_after_text_data_and_relocations() {
+        let mut x = vec![0u8; 64];
+        x[0] = b'H';
+        x[1] = b'U';
+        x[4..8].copy_from_slice(&0x0001_0000u32.to_be_bytes());
+        x[12..16].copy_from_slice(&4u32.to_be_bytes());
+        x[16..20].copy_from_slice(&2u32.to_be_bytes());
+        x[24..28].copy_from_slice(&2u32.to_be_bytes());
+        x[28..32].copy_from_slice(&22u32.to_be_bytes());
+        x.extend_from_slice(&[0x4e, 0x75, 0x4e, 0x75, 0x00, 0x01, 0x00, 0x02]);
+        x.extend_from_slice(&[0x02, 0x01, 0x00, 0x00, 0x00, 0x02, b'_', b'f', b'n', 0x00]);
+        x.extend_from_slice(&[
+            0x02, 0x00, 0x00, 0xe8, 0x00, 0x01, b'_', b'i', b'o', b'1', 0x00, 0x00,
+        ]);
+
+        let table = parse_x_symbols(&x).expect("parse");
+        assert_eq!(table.base_address, 0x0001_0000);
+        assert_eq!(
+            table.symbols,
+            vec![
+                XSymbol {
+                    kind: 0x0201,
+                    value: 2,
+                    name: b"_fn".to_vec(),
+                },
+                XSymbol {
+                    kind: 0x0200,
+                    value: 0x00e8_0001,
+                    name: b"_io1".to_vec(),
+                },
+            ]
+        );
+
+        x.truncate(x.len() - 3);
+        assert_eq!(parse_x_symbols(&x), Err(XFileError::UnexpectedEof));
+        assert_eq!(parse_x_symbols(b"MZ"), Err(XFileError::NotExecutable));
+    }
+}
@@ -2,6 +2,19 @@ use std::collections::{BTreeMap, HashMap};
 
 use crate::resolver::{ObjectSummary, SectionKind};
 
+/// Base-register-relative sections in the order startup code lays them out
+/// from the base register; matches the relative half of `___size_info`.
+pub const RELATIVE_SECTION_ORDER: [SectionKind; 8] = [
+    SectionKind::RData,
+    SectionKind::RBss,
+    SectionKind::RCommon,
+    SectionKind::RStack,
+    SectionKind::RLData,
+    SectionKind::RLBss,
+    SectionKind::RLCommon,
+    SectionKind::RLStack,
+];
+
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub struct ObjectPlacement {
     pub object_index: usize,
@@ -15,10 +28,51 @@ pub struct LayoutPlan {
     pub diagnostics: LayoutDiagnostics,
 }
 
+impl LayoutPlan {
+    /// Bytes the program occupies once loaded: text, data, bss, common and stack.
+    #[must_use]
+    pub fn image_size(&self) -> u32 {
+        [
+            SectionKind::Text,
+            SectionKind::Data,
+            SectionKind::Bss,
+            SectionKind::Common,
+            SectionKind::Stack,
+        ]
+        .iter()
+        .filter_map(|section| self.total_size_by_section.get(section))
+        .fold(0u32, |acc, size| acc.saturating_add(*size))
+    }
+
+    /// Offset of a relative section from the base register, or `None` for
+    /// sections that are not base-relative.
+    #[must_use]
+    pub fn relative_section_start(&self, section: SectionKind) -> Option<u32> {
+        let pos = RELATIVE_SECTION_ORDER.iter().position(|s| *s == section)?;
+        Some(
+            RELATIVE_SECTION_ORDER[..pos]
+                .iter()
+                .filter_map(|s| self.total_size_by_section.get(s))
+                .fold(0u32, |acc, size| acc.saturating_add(*size)),
+        )
+    }
+
+    /// Bytes the base-relative area occupies at run time.
+    #[must_use]
+    pub fn relative_area_size(&self) -> u32 {
+        RELATIVE_SECTION_ORDER
+            .iter()
+            .filter_map(|section| self.total_size_by_section.get(section))
+            .fold(0u32, |acc, size| acc.saturating_add(*size))
+    }
+}
+
 #[derive(Debug, Clone, Default, PartialEq, Eq)]
 pub struct LayoutDiagnostics {
     pub common_conflicts: usize,
     pub common_warnings: usize,
+    /// A section total did not fit in 32 bits and was clamped.
+    pub address_overflow: bool,
 }
 
 #[must_use]
@@ -47,6 +101,7 @@ pub fn plan_layout(objects: &[ObjectSummary]) -> LayoutPlan {
         SectionKind::RLStack,
     ];
 
+    let mut address_overflow = false;
     for section in section_order {
         let mut cursor = 0u32;
         for (idx, obj) in objects.iter().enumerate() {
@@ -55,17 +110,23 @@ pub fn plan_layout(objects: &[ObjectSummary]) -> LayoutPlan {
                 continue;
             }
 
-            cursor = align_up(cursor, obj.object_align.max(2));
-            placements[idx].by_section.insert(section, cursor);
-            cursor = cursor.saturating_add(size);
+            let placed = align_up(cursor, obj.object_align.max(2));
+            let end = placed.checked_add(size);
+            address_overflow |= placed < cursor || end.is_none();
+            placements[idx].by_section.insert(section, placed);
+            cursor = end.unwrap_or(u32::MAX);
         }
         total_size_by_section.insert(section, cursor);
     }
 
-    let (common_totals, diagnostics) = merge_common_symbols(objects);
+    let (common_totals, mut diagnostics) = merge_common_symbols(objects);
     total_size_by_section.insert(SectionKind::Common, common_totals.common);
     total_size_by_section.insert(SectionKind::RCommon, common_totals.rcommon);
     total_size_by_section.insert(SectionKind::RLCommon, common_totals.rlcommon);
+    let loaded = total_size_by_section
+        .values()
+        .try_fold(0u32, |acc, size| acc.checked_add(*size));
+    diagnostics.address_overflow = address_overflow || loaded.is_none();
 
     LayoutPlan {
         placements,
@@ -303,4 +364,38 @@ mod tests {
         assert_eq!(plan.diagnostics.common_conflicts, 1);
         assert_eq!(plan.diagnostics.common_warnings, 0);
     }
+
+    #[test]
+    fn places_relative_sections_from_the_base_register() {
+        let mut a = mk_summary(2, 2, 0);
+        a.declared_section_sizes.insert(SectionKind::RData, 6);
+        a.declared_section_sizes.insert(SectionKind::RBss, 4);
+        a.declared_section_sizes.insert(SectionKind::RLData, 8);
+        a.symbols.push(crate::resolver::Symbol {
+            name: b"_rbuf".to_vec(),
+            section: SectionKind::RCommon,
+            value: 10,
+        });
+        let mut b = mk_summary(2, 2, 0);
+        b.declared_section_sizes.insert(SectionKind::RData, 2);
+
+        let plan = plan_layout(&[a, b]);
+        assert_eq!(plan.placements[1].by_section.get(&SectionKind::RData), Some(&6));
+        assert_eq!(plan.relative_section_start(SectionKind::RData), Some(0));
+        assert_eq!(plan.relative_section_start(SectionKind::RBss), Some(8));
+        assert_eq!(plan.relative_section_start(SectionKind::RCommon), Some(12));
+        assert_eq!(plan.relative_section_start(SectionKind::RLData), Some(22));
+        assert_eq!(plan.relative_section_start(SectionKind::RLStack), Some(30));
+        assert_eq!(plan.relative_section_start(SectionKind::Data), None);
+        assert_eq!(plan.relative_area_size(), 30);
+        assert!(!plan.diagnostics.address_overflow);
+    }
+
+    #[test]
+    fn flags_section_totals_that_overflow() {
+        let plan = plan_layout(&[mk_summary(2, 0xffff_fff0, 0), mk_summary(2, 0x20, 0)]);
+        assert!(plan.diagnostics.address_overflow);
+        let plan = plan_layout(&[mk_summary(2, 0x8000_0000, 0x8000_0000)]);
+        assert!(plan.diagnostics.address_overflow);
+    }
 }
@@ -3,6 +3,7 @@ pub mod format;
 pub mod layout;
 pub mod linker;
 pub mod resolver;
+pub mod target;
 pub mod writer;
 
 pub use linker::run;
@@ -1,19 +1,33 @@
 use crate::cli::{
-    Args, BssMode, G2lkMode, OutputRequest, RelocationCheckMode, RuntimeConfig, SymbolMode,
+    Args, BssMode, CpuModel, DefineArg, DefineOperand, EntryArg, G2lkMode, JustSymbolsArg,
+    MaxSizeArg, OutputRequest, RelocationCheckMode, RuntimeConfig, ScdMode, SizeReportMode,
+    SymbolMode,
 };
 use crate::format::FormatError;
-use crate::format::obj::{Command, ObjectFile, parse_object};
-use crate::layout::plan_layout;
-use crate::resolver::resolve_object;
-use crate::resolver::ObjectSummary;
+use crate::format::obj::{Command, ObjectFile, parse_object_at};
+use crate::format::symfile::parse_symbol_file;
+use crate::format::xfile::parse_x_symbols;
+use crate::layout::{LayoutPlan, plan_layout};
+use crate::resolver::{apply_wraps, resolve_object};
+use crate::resolver::{ObjectSummary, SectionKind};
+use crate::target::{layout_end, Cpu, X68000};
 use crate::writer::{
-    BssPolicy, OutputFormat, OutputOptions, RelocationCheck, SymbolTablePolicy, write_map,
-    write_output,
+    BssPolicy, OutputFormat, OutputOptions, RelocationCheck, SizeBudget, SizeReportFormat, SymbolFilter,
+    SymbolTablePolicy, check_cpu_rules, check_size_budgets, diff_size_reports, insert_branch_islands, render_memory_usage, section_tag,
+    validate_scd, write_map, write_output, write_size_report,
 };
 use std::env;
-use std::collections::{HashMap, HashSet, VecDeque};
+use std::fmt::Write as _;
+use std::collections::{BTreeMap, HashMap, HashSet, VecDeque};
 use std::path::{Path, PathBuf};
 
+mod overlay;
+
+use overlay::{
+    inject_overlay_placeholders, link_overlays, load_overlay_groups, validate_overlay_references,
+    write_overlays, OverlayGroup,
+};
+
 /// Runs the complete link pipeline from CLI args.
 ///
 /// # Errors
@@ -22,6 +36,10 @@ pub fn run(args: Args) -> anyhow::Result<()> {
     validate_args(&args)?;
     let runtime = args.runtime_config();
     print_title_if_needed(runtime);
+    if let [old, new] = args.size_diff.as_slice() {
+        print!("{}", diff_size_reports(old, new)?);
+        return Ok(());
+    }
     let expanded_inputs = expand_inputs(&args)?;
     let prepared = prepare_objects(args, runtime, expanded_inputs)?;
     emit_outputs(prepared)
@@ -34,6 +52,7 @@ struct PreparedLink {
     objects: Vec<ObjectFile>,
     summaries: Vec<ObjectSummary>,
     input_names: Vec<String>,
+    overlays: Vec<OverlayGroup>,
 }
 
 fn validate_args(args: &Args) -> anyhow::Result<()> {
@@ -42,6 +61,43 @@ fn validate_args(args: &Args) -> anyhow::Result<()> {
             anyhow::bail!("align size must be power of two in [2, 256]: {align}");
         }
     }
+    if args.self_relocate && (!args.r_format || args.make_mcs) {
+        anyhow::bail!("--self-relocate requires -r and cannot be used with --makemcs");
+    }
+    let formats = [
+        args.r_format || args.make_mcs,
+        args.make_device,
+        args.z_format,
+        args.make_prg,
+    ];
+    if formats.iter().filter(|&&v| v).count() > 1 {
+        anyhow::bail!("only one of -r/--makemcs, --device, --z-format and --prg may be given");
+    }
+    if args.z_format && args.base_address.is_none() {
+        anyhow::bail!("--z-format requires a fixed base address (-b)");
+    }
+    if !args.overlays.is_empty() {
+        // Root-to-overlay references are either absolute, for a root the
+        // loader never moves, or relocated along with a plain .x root.
+        let fixed_root = args.z_format || (args.r_format && !args.make_mcs && !args.self_relocate);
+        let x_root = !(args.r_format || args.make_mcs || args.make_device || args.z_format || args.make_prg);
+        if !fixed_root && !x_root {
+            anyhow::bail!("--overlay requires an .x root or a fixed-address root (--z-format or -r)");
+        }
+        if fixed_root && args.base_address.is_none() {
+            anyhow::bail!("--overlay requires a fixed base address (-b)");
+        }
+        if x_root && args.base_address.is_some() {
+            anyhow::bail!("--overlay with an .x root cannot take -b: the overlays are relocated with it");
+        }
+    }
+    // The sidecar exists to carry the symbols the loaded image cannot.
+    if args.debug_file.is_some() && (args.cut_symbols || args.strip_all) {
+        anyhow::bail!("--debug-file cannot be used with -x or --strip-all: the debug file would have no symbols");
+    }
+    if args.stack_size.is_some_and(|size| size == 0 || size & 1 != 0) {
+        anyhow::bail!("stack size must be a non-zero even number");
+    }
     Ok(())
 }
 
@@ -51,6 +107,12 @@ fn print_title_if_needed(runtime: RuntimeConfig) {
     }
 }
 
+fn print_warning(runtime: RuntimeConfig, message: &str) {
+    if !runtime.warn_off {
+        eprintln!("Warning: {message}");
+    }
+}
+
 fn expand_inputs(args: &Args) -> anyhow::Result<Vec<PathBuf>> {
     let mut expanded_inputs = args.inputs.clone();
     for indirect in &args.indirect_files {
@@ -68,17 +130,45 @@ fn prepare_objects(
     runtime: RuntimeConfig,
     expanded_inputs: Vec<PathBuf>,
 ) -> anyhow::Result<PreparedLink> {
-    let (objects, summaries, input_names) =
-        load_objects_with_requests_paths(&expanded_inputs, runtime.verbose)?;
-    let mut objects = objects;
-    let mut summaries = summaries;
-    let mut input_names = input_names;
+    let mut load_ctx = LoadContext {
+        verbose: runtime.verbose,
+        trace_symbols: args.trace_symbols.iter().map(|s| s.as_bytes().to_vec()).collect(),
+        wraps: args.wraps.iter().map(|s| s.as_bytes().to_vec()).collect(),
+        preloaded: args
+            .just_symbols
+            .iter()
+            .map(load_just_symbols)
+            .collect::<anyhow::Result<Vec<_>>>()?,
+        extractions: Vec::new(),
+    };
+    let seeds_at = load_ctx.preloaded.len();
     if !args.defines.is_empty() {
-        inject_define_symbols(&args, &mut objects, &mut summaries, &mut input_names);
+        load_ctx.preloaded.push(define_symbols_object(&args.defines));
+    }
+    let symbol_defs = read_symbol_files(&args.symbol_files, runtime)?;
+    if !symbol_defs.is_empty() {
+        load_ctx.preloaded.push(symbol_file_object(&symbol_defs));
+    }
+    let seeds = load_ctx.preloaded.len() - seeds_at;
+    let (mut objects, mut summaries, mut input_names) =
+        load_objects_with_requests_paths(&expanded_inputs, &mut load_ctx)?;
+    prune_just_symbols(&mut objects, &mut summaries, args.just_symbols.len());
+    // *DEFINE* and *SYMFILE* are preloaded only for archive selection; they
+    // still link after the inputs.
+    objects[seeds_at..].rotate_left(seeds);
+    summaries[seeds_at..].rotate_left(seeds);
+    input_names[seeds_at..].rotate_left(seeds);
+    prune_symbol_file_definitions(&symbol_defs, runtime, &mut objects, &mut summaries, &mut input_names);
+    drop_definitions_overridden_by_define(&mut summaries, &input_names);
+    if let Some(path) = args.why_extract.as_deref() {
+        write_why_extract(path, &load_ctx.extractions)?;
     }
     if runtime.section_info {
         inject_section_info_object(&mut objects, &mut summaries, &mut input_names);
     }
+    if let Some(size) = args.stack_size {
+        inject_stack_object(size, &mut objects, &mut summaries, &mut input_names);
+    }
     if let Some(align) = args.align {
         for s in &mut summaries {
             // Apply global default align only to objects that still have the default value.
@@ -87,8 +177,18 @@ fn prepare_objects(
             }
         }
     }
+    let overlays = load_overlay_groups(&args.overlays, &summaries, &load_ctx)?;
+    if !overlays.is_empty() {
+        validate_overlay_references(&overlays)?;
+        inject_overlay_placeholders(&overlays, &mut objects, &mut summaries, &mut input_names);
+    }
     validate_unresolved_symbols(&summaries, &input_names)?;
-    validate_start_address_uniqueness(&summaries, &input_names)?;
+    if !args.allow_multiple_definition {
+        validate_multiple_definitions(&summaries, &input_names)?;
+    }
+    if args.entry.is_none() {
+        validate_start_address_uniqueness(&summaries, &input_names)?;
+    }
 
     Ok(PreparedLink {
         args,
@@ -97,23 +197,102 @@ fn prepare_objects(
         objects,
         summaries,
         input_names,
+        overlays,
     })
 }
 
+/// Rejects layouts whose addresses overflow 32 bits, and with `--target-check`
+/// those that do not fit the X68000 memory map; ROM and accelerator builds
+/// legitimately use bases outside main RAM, so the map is opt-in.
+fn validate_target_layout(
+    base_address: Option<u32>,
+    layout: &LayoutPlan,
+    target_check: bool,
+) -> anyhow::Result<()> {
+    if layout.diagnostics.address_overflow {
+        anyhow::bail!("section sizes overflow the 32-bit address space");
+    }
+    layout_end("image", layout, base_address.unwrap_or(0))?;
+    if target_check {
+        if let Some(base) = base_address {
+            X68000.check_range("base address", base, 0)?;
+        }
+        X68000.check_layout("image", layout, base_address)?;
+    }
+    Ok(())
+}
+
+/// Validates SCD debug info per `--scd`: strict fails listing every problem,
+/// lenient warns and drops only the offending objects' SCD, strip drops it all.
+fn apply_scd_mode(
+    mode: ScdMode,
+    runtime: RuntimeConfig,
+    objects: &mut [ObjectFile],
+    summaries: &[ObjectSummary],
+    input_names: &[String],
+) -> anyhow::Result<()> {
+    if mode == ScdMode::Strip {
+        for obj in objects.iter_mut() {
+            obj.scd_tail.clear();
+        }
+        return Ok(());
+    }
+    let problems = validate_scd(objects, input_names, summaries);
+    if problems.is_empty() {
+        return Ok(());
+    }
+    if mode == ScdMode::Strict {
+        let messages = problems.iter().map(|p| p.message.as_str()).collect::<Vec<_>>();
+        anyhow::bail!("{}", messages.join("\n"));
+    }
+    for problem in problems {
+        print_warning(runtime, &format!("{}; its SCD debug info is dropped", problem.message));
+        objects[problem.object].scd_tail.clear();
+    }
+    Ok(())
+}
+
 fn emit_outputs(prepared: PreparedLink) -> anyhow::Result<()> {
     let PreparedLink {
         args,
         runtime,
         expanded_inputs,
-        objects,
+        mut objects,
         mut summaries,
         input_names,
+        mut overlays,
     } = prepared;
     let args = &args;
+    apply_scd_mode(args.scd_mode(), runtime, &mut objects, &summaries, &input_names)?;
+    if args.branch_islands {
+        let stubs = insert_branch_islands(&mut objects, &mut summaries)?;
+        if runtime.verbose {
+            println!("branch islands: {stubs}");
+        }
+    }
     let layout = plan_layout(&summaries);
+    validate_target_layout(args.base_address, &layout, args.target_check)?;
+    if !args.defines.is_empty() {
+        resolve_define_symbols(&args.defines, &mut summaries, &layout, &input_names)?;
+    }
     if runtime.section_info {
         update_section_info_rsize(&mut summaries, &layout);
     }
+    if let Some(entry) = &args.entry {
+        apply_entry_override(entry, &mut summaries, &layout, runtime.base_address)?;
+    }
+    let overlay_layouts = if overlays.is_empty() {
+        None
+    } else {
+        Some(link_overlays(
+            &mut overlays,
+            &mut summaries,
+            &layout,
+            &input_names,
+            args.base_address,
+            args.target_check,
+        )?)
+    };
     if runtime.verbose {
         println!("layout totals:");
         for (section, size) in &layout.total_size_by_section {
@@ -125,34 +304,21 @@ fn emit_outputs(prepared: PreparedLink) -> anyhow::Result<()> {
         );
     }
 
+    check_size_budgets(&size_budgets(&args.max_sizes), &summaries, &layout, &input_names)?;
+    if let Some(model) = args.cpu {
+        for warning in check_cpu_rules(target_cpu(model), &objects, &input_names, &layout)? {
+            print_warning(runtime, &warning);
+        }
+    }
+
     let output = resolve_output_path(args, &expanded_inputs);
     let output_s = output.to_string_lossy();
-    let options = OutputOptions {
-        format: match runtime.output_request {
-            OutputRequest::X => OutputFormat::X,
-            OutputRequest::R => OutputFormat::R,
-            OutputRequest::Mcs => OutputFormat::Mcs,
-        },
-        relocation_check: match runtime.relocation_check {
-            RelocationCheckMode::Strict => RelocationCheck::Strict,
-            RelocationCheckMode::Skip => RelocationCheck::Skip,
-        },
-        bss_policy: match runtime.bss_mode {
-            BssMode::Include => BssPolicy::Include,
-            BssMode::Omit => BssPolicy::Omit,
-        },
-        symbol_table: match runtime.symbol_mode {
-            SymbolMode::Keep => SymbolTablePolicy::Keep,
-            SymbolMode::Cut => SymbolTablePolicy::Cut,
-        },
-        base_address: runtime.base_address,
-        load_mode: runtime.load_mode,
-        section_info: runtime.section_info,
-        g2lk_mode: matches!(runtime.g2lk_mode, G2lkMode::On),
-    };
+    let mut options = output_options(args, runtime);
+    options.debug_file = resolve_debug_output(args.debug_file.as_deref(), &output)
+        .map(|path| path.to_string_lossy().into_owned());
     write_output(
         output_s.as_ref(),
-        options,
+        &options,
         &objects,
         &input_names,
         &summaries,
@@ -161,6 +327,15 @@ fn emit_outputs(prepared: PreparedLink) -> anyhow::Result<()> {
     if runtime.verbose {
         println!("wrote output: {}", output.display());
     }
+    if let Some((overlay_base, layouts)) = &overlay_layouts {
+        write_overlays(
+            &overlays,
+            layouts,
+            *overlay_base,
+            options.g2lk_mode,
+            runtime.verbose,
+        )?;
+    }
     if let Some(map_output) = resolve_map_output(args.map.as_deref(), Some(output.as_path()), &expanded_inputs) {
         let map_output_s = map_output.to_string_lossy();
         write_map(
@@ -174,6 +349,7 @@ fn emit_outputs(prepared: PreparedLink) -> anyhow::Result<()> {
             println!("wrote map: {}", map_output.display());
         }
     }
+    write_size_outputs(args, output_s.as_ref(), &summaries, &layout, &input_names)?;
 
     if runtime.verbose {
         println!("rhlk: parsed {} input file(s)", input_names.len());
@@ -181,6 +357,104 @@ fn emit_outputs(prepared: PreparedLink) -> anyhow::Result<()> {
     Ok(())
 }
 
+/// `--size-report` and `--print-memory-usage` for the main output.
+fn write_size_outputs(
+    args: &Args,
+    output: &str,
+    summaries: &[ObjectSummary],
+    layout: &LayoutPlan,
+    input_names: &[String],
+) -> anyhow::Result<()> {
+    if let Some(report_output) = args.size_report.as_deref() {
+        let format = match args.size_format {
+            SizeReportMode::Text => SizeReportFormat::Text,
+            SizeReportMode::Json => SizeReportFormat::Json,
+        };
+        write_size_report(
+            output,
+            (!report_output.is_empty()).then_some(report_output),
+            format,
+            summaries,
+            layout,
+            input_names,
+        )?;
+    }
+    if args.print_memory_usage {
+        print!("{}", render_memory_usage(&size_budgets(&args.max_sizes), layout));
+    }
+    Ok(())
+}
+
+fn size_budgets(max_sizes: &[MaxSizeArg]) -> Vec<SizeBudget> {
+    max_sizes
+        .iter()
+        .filter_map(|arg| SizeBudget::named(&arg.section, arg.limit))
+        .collect()
+}
+
+fn target_cpu(model: CpuModel) -> Cpu {
+    match model {
+        CpuModel::M68000 => Cpu::M68000,
+        CpuModel::M68020 => Cpu::M68020,
+        CpuModel::M68030 => Cpu::M68030,
+        CpuModel::M68040 => Cpu::M68040,
+        CpuModel::M68060 => Cpu::M68060,
+    }
+}
+
+fn output_options(args: &Args, runtime: RuntimeConfig) -> OutputOptions {
+    OutputOptions {
+        format: match runtime.output_request {
+            OutputRequest::X => OutputFormat::X,
+            OutputRequest::R => OutputFormat::R,
+            OutputRequest::Mcs => OutputFormat::Mcs,
+            OutputRequest::Sys => OutputFormat::Sys,
+            OutputRequest::Z => OutputFormat::Z,
+            OutputRequest::Prg => OutputFormat::Prg,
+        },
+        relocation_check: match runtime.relocation_check {
+            RelocationCheckMode::Strict => RelocationCheck::Strict,
+            RelocationCheckMode::Skip => RelocationCheck::Skip,
+            RelocationCheckMode::SelfRelocate => RelocationCheck::SelfRelocate,
+        },
+        bss_policy: match runtime.bss_mode {
+            BssMode::Include => BssPolicy::Include,
+            BssMode::Omit => BssPolicy::Omit,
+        },
+        symbol_table: match runtime.symbol_mode {
+            SymbolMode::Keep => SymbolTablePolicy::Keep,
+            SymbolMode::Cut => SymbolTablePolicy::Cut,
+        },
+        symbol_filter: SymbolFilter {
+            keep: args.keep_symbols.iter().map(|s| s.as_bytes().to_vec()).collect(),
+            strip: args.strip_symbols.iter().map(|s| s.as_bytes().to_vec()).collect(),
+        },
+        base_address: runtime.base_address,
+        load_mode: runtime.load_mode,
+        section_info: runtime.section_info,
+        g2lk_mode: matches!(runtime.g2lk_mode, G2lkMode::On),
+        debug_file: None,
+    }
+}
+
+fn write_why_extract(path: &Path, extractions: &[ArchiveExtraction]) -> anyhow::Result<()> {
+    let mut text = String::from("reference\textracted\tsymbol\n");
+    for ext in extractions {
+        let _ = writeln!(
+            text,
+            "{}\t{}\t{}",
+            ext.reference,
+            ext.extracted,
+            String::from_utf8_lossy(&ext.symbol)
+        );
+    }
+    if path == Path::new("-") {
+        print!("{text}");
+        return Ok(());
+    }
+    std::fs::write(path, text).map_err(|e| anyhow::anyhow!("failed to write {}: {e}", path.display()))
+}
+
 fn validate_start_address_uniqueness(
     summaries: &[ObjectSummary],
     input_names: &[String],
@@ -246,18 +520,174 @@ fn resolve_lib_inputs(args: &Args) -> anyhow::Result<Vec<PathBuf>> {
     Ok(out)
 }
 
-fn inject_define_symbols(
-    args: &Args,
+/// Builds the `*DEFINE*` object for `-d`; its references to other symbols are
+/// xrefs, so it is preloaded to let archive selection see them.
+fn define_symbols_object(defines: &[DefineArg]) -> (String, ObjectFile, ObjectSummary) {
+    let mut commands = Vec::new();
+    let mut xrefs = Vec::<&str>::new();
+    for def in defines {
+        // Symbolic definitions start as placeholders and are fixed up after layout.
+        commands.push(Command::DefineSymbol {
+            section: 0x00,
+            value: def.expr.constant().unwrap_or(0),
+            name: def.name.as_bytes().to_vec(),
+        });
+        for sym in def.expr.symbols() {
+            if !xrefs.contains(&sym) {
+                xrefs.push(sym);
+            }
+        }
+    }
+    for (idx, name) in xrefs.iter().enumerate() {
+        commands.push(Command::DefineSymbol {
+            section: 0xff,
+            value: u32::try_from(idx + 1).unwrap_or(u32::MAX),
+            name: name.as_bytes().to_vec(),
+        });
+    }
+    commands.push(Command::End);
+    let obj = ObjectFile {
+        commands,
+        scd_tail: Vec::new(),
+    };
+    let sum = resolve_object(&obj);
+    ("*DEFINE*".to_string(), obj, sum)
+}
+
+/// Makes `-d` authoritative: an object's definition of a name `*DEFINE*` also
+/// defines is dropped, so it neither counts as a multiple definition nor wins
+/// under `--allow-multiple-definition`.
+fn drop_definitions_overridden_by_define(summaries: &mut [ObjectSummary], input_names: &[String]) {
+    let Some(define_idx) = input_names.iter().position(|n| n == "*DEFINE*") else {
+        return;
+    };
+    let defined = summaries[define_idx]
+        .symbols
+        .iter()
+        .map(|sym| sym.name.clone())
+        .collect::<HashSet<_>>();
+    for (idx, summary) in summaries.iter_mut().enumerate() {
+        if idx != define_idx {
+            summary.symbols.retain(|sym| !defined.contains(&sym.name));
+        }
+    }
+}
+
+/// Reads every `--symbol-file` as `(name, value, "path:line")`, rejecting
+/// conflicting redefinitions and warning about repeated identical ones.
+fn read_symbol_files(paths: &[PathBuf], runtime: RuntimeConfig) -> anyhow::Result<Vec<(Vec<u8>, u32, String)>> {
+    let mut defs = Vec::<(Vec<u8>, u32, String)>::new();
+    let mut seen = HashMap::<Vec<u8>, usize>::new();
+    for path in paths {
+        let bytes = std::fs::read(path)
+            .map_err(|_| anyhow::anyhow!("ファイルがありません: {}", display_name(path)))?;
+        let parsed = parse_symbol_file(&bytes)
+            .map_err(|e| anyhow::anyhow!("{}: {e}", path.display()))?;
+        for def in parsed {
+            let location = format!("{}:{}", path.display(), def.line);
+            if let Some(&prev) = seen.get(&def.name) {
+                let (_, prev_value, prev_location) = &defs[prev];
+                let name = String::from_utf8_lossy(&def.name);
+                if *prev_value != def.value {
+                    anyhow::bail!(
+                        "シンボルが二重定義されています: {name} in {prev_location} and {location}"
+                    );
+                }
+                print_warning(
+                    runtime,
+                    &format!("duplicate symbol definition: {name} in {prev_location} and {location}"),
+                );
+                continue;
+            }
+            seen.insert(def.name.clone(), defs.len());
+            defs.push((def.name, def.value, location));
+        }
+    }
+    Ok(defs)
+}
+
+/// Builds the `*SYMFILE*` object holding every symbol file definition as an
+/// absolute symbol; it is preloaded so archive selection sees them.
+fn symbol_file_object(defs: &[(Vec<u8>, u32, String)]) -> (String, ObjectFile, ObjectSummary) {
+    let mut commands = defs
+        .iter()
+        .map(|(name, value, _)| Command::DefineSymbol {
+            section: 0x00,
+            value: *value,
+            name: name.clone(),
+        })
+        .collect::<Vec<_>>();
+    commands.push(Command::End);
+    let obj = ObjectFile {
+        commands,
+        scd_tail: Vec::new(),
+    };
+    let sum = resolve_object(&obj);
+    ("*SYMFILE*".to_string(), obj, sum)
+}
+
+/// Drops the `*SYMFILE*` definitions nothing refers to, so large register/ROM
+/// tables do not bloat the output symbol table, and warns about each of them.
+/// The object itself goes when none are left.
+fn prune_symbol_file_definitions(
+    defs: &[(Vec<u8>, u32, String)],
+    runtime: RuntimeConfig,
     objects: &mut Vec<ObjectFile>,
     summaries: &mut Vec<ObjectSummary>,
     input_names: &mut Vec<String>,
 ) {
+    let Some(idx) = input_names.iter().position(|n| n == "*SYMFILE*") else {
+        return;
+    };
+    let referenced = summaries
+        .iter()
+        .flat_map(|s| s.xrefs.iter().map(|x| x.name.clone()))
+        .collect::<HashSet<_>>();
+    for (name, _, location) in defs {
+        if !referenced.contains(name) {
+            print_warning(
+                runtime,
+                &format!("unused symbol definition: {} ({location})", String::from_utf8_lossy(name)),
+            );
+        }
+    }
+    summaries[idx].symbols.retain(|sym| referenced.contains(&sym.name));
+    objects[idx].commands.retain(|cmd| match cmd {
+        Command::DefineSymbol { name, .. } => referenced.contains(name),
+        _ => true,
+    });
+    if summaries[idx].symbols.is_empty() {
+        objects.remove(idx);
+        summaries.remove(idx);
+        input_names.remove(idx);
+    }
+}
+
+/// Reads the symbol table of an already linked `.x` as absolute definitions.
+///
+/// Section-relative entries are rebased on `@BASE` when given, otherwise on the
+/// base address recorded in the header, so references never need relocation.
+fn load_just_symbols(arg: &JustSymbolsArg) -> anyhow::Result<(String, ObjectFile, ObjectSummary)> {
+    let bytes = std::fs::read(&arg.path)
+        .map_err(|_| anyhow::anyhow!("ファイルがありません: {}", display_name(&arg.path)))?;
+    let table = parse_x_symbols(&bytes)
+        .map_err(|e| anyhow::anyhow!("{}: {e}", display_name(&arg.path)))?;
+    let base = arg.load_base.unwrap_or(table.base_address);
+    let mut seen = HashSet::<Vec<u8>>::new();
     let mut commands = Vec::new();
-    for def in &args.defines {
+    for sym in table.symbols {
+        let value = match sym.kind {
+            0x0200 => sym.value,
+            0x0201..=0x0204 => base.wrapping_add(sym.value),
+            _ => continue,
+        };
+        if sym.name.first() == Some(&b'*') || !seen.insert(sym.name.clone()) {
+            continue;
+        }
         commands.push(Command::DefineSymbol {
             section: 0x00,
-            value: def.value,
-            name: def.name.as_bytes().to_vec(),
+            value,
+            name: sym.name,
         });
     }
     commands.push(Command::End);
@@ -266,9 +696,163 @@ fn inject_define_symbols(
         scd_tail: Vec::new(),
     };
     let sum = resolve_object(&obj);
-    objects.push(obj);
-    summaries.push(sum);
-    input_names.push("*DEFINE*".to_string());
+    Ok((arg.path.to_string_lossy().to_string(), obj, sum))
+}
+
+/// Drops `--just-symbols` definitions nothing refers to, keeping them out of the output.
+fn prune_just_symbols(objects: &mut [ObjectFile], summaries: &mut [ObjectSummary], count: usize) {
+    if count == 0 {
+        return;
+    }
+    let referenced = summaries
+        .iter()
+        .flat_map(|s| s.xrefs.iter().map(|x| x.name.clone()))
+        .collect::<HashSet<_>>();
+    for (obj, sum) in objects.iter_mut().zip(summaries.iter_mut()).take(count) {
+        sum.symbols.retain(|sym| referenced.contains(&sym.name));
+        obj.commands.retain(|cmd| match cmd {
+            Command::DefineSymbol { name, .. } => referenced.contains(name),
+            _ => true,
+        });
+    }
+}
+
+/// Evaluates symbolic `-d` expressions once section placement is known.
+///
+/// A result with one net positive relocatable term keeps that term's section,
+/// so it gets relocated like any other symbol of that section; terms that
+/// cancel out (`end-start`) leave an absolute value.
+fn resolve_define_symbols(
+    defines: &[DefineArg],
+    summaries: &mut [ObjectSummary],
+    layout: &LayoutPlan,
+    input_names: &[String],
+) -> anyhow::Result<()> {
+    let Some(define_idx) = input_names.iter().position(|n| n == "*DEFINE*") else {
+        return Ok(());
+    };
+    let mut pending = defines
+        .iter()
+        .filter(|d| d.expr.constant().is_none())
+        .map(|d| d.name.as_str())
+        .collect::<HashSet<_>>();
+    for def in defines {
+        if def.expr.constant().is_some() {
+            continue;
+        }
+        let mut offset = 0i64;
+        let mut net = BTreeMap::<SectionKind, i32>::new();
+        for term in &def.expr.terms {
+            let value = match &term.operand {
+                DefineOperand::Number(v) => i64::from(*v),
+                DefineOperand::Symbol(name) => {
+                    if pending.contains(name.as_str()) {
+                        anyhow::bail!("不正な式 in *DEFINE*: {} refers to {name} before it is defined", def.name);
+                    }
+                    let (section, pos) = defined_symbol_position(summaries, layout, name.as_bytes())
+                        .ok_or_else(|| unplaceable_symbol(summaries, name, "*DEFINE*"))?;
+                    if section != SectionKind::Abs {
+                        *net.entry(section).or_insert(0) += if term.negate { -1 } else { 1 };
+                    }
+                    i64::from(pos)
+                }
+            };
+            offset += if term.negate { -value } else { value };
+        }
+        net.retain(|_, n| *n != 0);
+        let (section, value) = match net.into_iter().collect::<Vec<_>>().as_slice() {
+            [] => (SectionKind::Abs, u32::try_from(offset & 0xffff_ffff).unwrap_or(0)),
+            [(section, 1)] => {
+                let value = u32::try_from(offset).map_err(|_| {
+                    anyhow::anyhow!("不正な式 in *DEFINE*: {} is outside its section", def.name)
+                })?;
+                (*section, value)
+            }
+            _ => anyhow::bail!("不正な式 in *DEFINE*: {}", def.name),
+        };
+        // *DEFINE* owns no section space, so a section-relative value is already final.
+        for sym in &mut summaries[define_idx].symbols {
+            if sym.name == def.name.as_bytes() {
+                sym.section = section;
+                sym.value = value;
+            }
+        }
+        pending.remove(def.name.as_str());
+    }
+    Ok(())
+}
+
+/// Replaces every object's start address with the `--entry` target.
+fn apply_entry_override(
+    entry: &EntryArg,
+    summaries: &mut [ObjectSummary],
+    layout: &LayoutPlan,
+    base_address: u32,
+) -> anyhow::Result<()> {
+    let (section, addr) = match entry {
+        EntryArg::Address(addr) => {
+            let Some(offset) = addr.checked_sub(base_address) else {
+                anyhow::bail!("entry address {addr:#x} is below the base address {base_address:#x}");
+            };
+            (0x01, offset)
+        }
+        EntryArg::Symbol(name) => match defined_symbol_position(summaries, layout, name.as_bytes()) {
+            Some((SectionKind::Text, pos)) => (0x01, pos),
+            Some((SectionKind::Data, pos)) => (0x02, pos),
+            Some((SectionKind::Bss, pos)) => (0x03, pos),
+            Some(_) => anyhow::bail!("entry symbol is not in text, data or bss: {name}"),
+            None => return Err(unplaceable_symbol(summaries, name, "--entry")),
+        },
+    };
+    if addr & 1 != 0 {
+        anyhow::bail!("entry address is odd: {addr:#x}");
+    }
+    for summary in summaries.iter_mut() {
+        summary.start_address = None;
+    }
+    if let Some(first) = summaries.first_mut() {
+        first.start_address = Some((section, addr));
+    }
+    Ok(())
+}
+
+/// Explains why `defined_symbol_position` found nothing for `name`: it is
+/// either undefined or defined in a section it cannot place (common, r-sections).
+fn unplaceable_symbol(summaries: &[ObjectSummary], name: &str, what: &str) -> anyhow::Error {
+    let section = summaries
+        .iter()
+        .flat_map(|s| s.symbols.iter())
+        .find(|sym| sym.name == name.as_bytes())
+        .map(|sym| sym.section);
+    match section {
+        Some(section) => anyhow::anyhow!(
+            "{name} is in an unsupported section ({}) in {what}",
+            section_tag(section)
+        ),
+        None => anyhow::anyhow!("未定義シンボル: {name} in {what}"),
+    }
+}
+
+fn defined_symbol_position(
+    summaries: &[ObjectSummary],
+    layout: &LayoutPlan,
+    name: &[u8],
+) -> Option<(SectionKind, u32)> {
+    summaries.iter().enumerate().find_map(|(idx, summary)| {
+        let sym = summary.symbols.iter().find(|sym| sym.name == name)?;
+        match sym.section {
+            SectionKind::Abs => Some((SectionKind::Abs, sym.value)),
+            SectionKind::Text | SectionKind::Data | SectionKind::Bss | SectionKind::Stack => {
+                let base = layout.placements[idx]
+                    .by_section
+                    .get(&sym.section)
+                    .copied()
+                    .unwrap_or(0);
+                Some((sym.section, base.saturating_add(sym.value)))
+            }
+            _ => None,
+        }
+    })
 }
 
 fn inject_section_info_object(
@@ -356,62 +940,50 @@ fn inject_section_info_object(
     input_names.insert(0, "*SYSTEM*".to_string());
 }
 
-fn update_section_info_rsize(summaries: &mut [ObjectSummary], layout: &crate::layout::LayoutPlan) {
-    use crate::resolver::SectionKind;
-    let rsize = layout
-        .total_size_by_section
-        .get(&SectionKind::RData)
-        .copied()
-        .unwrap_or(0)
-        .saturating_add(
-            layout
-                .total_size_by_section
-                .get(&SectionKind::RBss)
-                .copied()
-                .unwrap_or(0),
-        )
-        .saturating_add(
-            layout
-                .total_size_by_section
-                .get(&SectionKind::RCommon)
-                .copied()
-                .unwrap_or(0),
-        )
-        .saturating_add(
-            layout
-                .total_size_by_section
-                .get(&SectionKind::RStack)
-                .copied()
-                .unwrap_or(0),
-        )
-        .saturating_add(
-            layout
-                .total_size_by_section
-                .get(&SectionKind::RLData)
-                .copied()
-                .unwrap_or(0),
-        )
-        .saturating_add(
-            layout
-                .total_size_by_section
-                .get(&SectionKind::RLBss)
-                .copied()
-                .unwrap_or(0),
-        )
-        .saturating_add(
-            layout
-                .total_size_by_section
-                .get(&SectionKind::RLCommon)
-                .copied()
-                .unwrap_or(0),
-        )
-        .saturating_add(
-            layout
-                .total_size_by_section
-                .get(&SectionKind::RLStack)
-                .copied()
-                .unwrap_or(0),
-        );
+/// Appends `*STACK*`, which reserves `size` bytes of stack after every other
+/// object's and marks both ends with `___stack_bottom`/`___stack_top`.
+fn inject_stack_object(
+    size: u32,
+    objects: &mut Vec<ObjectFile>,
+    summaries: &mut Vec<ObjectSummary>,
+    input_names: &mut Vec<String>,
+) {
+    let commands = vec![
+        Command::SourceFile {
+            size: 0,
+            name: b"*STACK*".to_vec(),
+        },
+        Command::Header {
+            section: 0x04,
+            size,
+            name: b"stack".to_vec(),
+        },
+        Command::DefineSymbol {
+            section: 0x04,
+            value: 0,
+            name: b"___stack_bottom".to_vec(),
+        },
+        Command::DefineSymbol {
+            section: 0x04,
+            value: size,
+            name: b"___stack_top".to_vec(),
+        },
+        Command::ChangeSection { section: 0x04 },
+        Command::DefineSpace { size },
+        Command::End,
+    ];
+    let obj = ObjectFile {
+        commands,
+        scd_tail: Vec::new(),
+    };
+    let sum = resolve_object(&obj);
+    objects.push(obj);
+    summaries.push(sum);
+    input_names.push("*STACK*".to_string());
+}
+
+fn update_section_info_rsize(summaries: &mut [ObjectSummary], layout: &LayoutPlan) {
+    let rsize = layout.relative_area_size();
     for summary in summaries.iter_mut() {
         for sym in &mut summary.symbols {
             if sym.section == SectionKind::Abs && sym.name == b"___rsize" {
@@ -446,6 +1018,18 @@ fn resolve_output_path(args: &Args, inputs: &[PathBuf]) -> PathBuf {
         return base;
     }
     let mut out = base;
+    if args.make_prg {
+        out.set_extension("prg");
+        return out;
+    }
+    if args.z_format {
+        out.set_extension("z");
+        return out;
+    }
+    if args.make_device {
+        out.set_extension("sys");
+        return out;
+    }
     if args.make_mcs {
         out.set_extension("mcs");
         return out;
@@ -470,25 +1054,39 @@ fn resolve_map_output(
     output_opt: Option<&Path>,
     inputs: &[PathBuf],
 ) -> Option<PathBuf> {
-    let raw = map_opt?;
+    resolve_side_output(map_opt, output_opt, inputs, "map")
+}
+
+/// `--debug-file` defaults to the output name with a `.dbg` extension.
+fn resolve_debug_output(debug_opt: Option<&str>, output: &Path) -> Option<PathBuf> {
+    resolve_side_output(debug_opt, Some(output), &[], "dbg")
+}
+
+fn resolve_side_output(
+    opt: Option<&str>,
+    output_opt: Option<&Path>,
+    inputs: &[PathBuf],
+    extension: &str,
+) -> Option<PathBuf> {
+    let raw = opt?;
     if !raw.is_empty() {
         let p = PathBuf::from(raw);
         if p.extension().is_some() {
             return Some(p);
         }
-        return Some(p.with_extension("map"));
+        return Some(p.with_extension(extension));
     }
     let p = output_opt
         .map(Path::to_path_buf)
         .or_else(|| inputs.first().cloned())?;
     let stem = p.file_stem()?.to_string_lossy();
     let mut out = if let Some(parent) = p.parent() {
-        parent.join(format!("{stem}.map"))
+        parent.join(format!("{stem}.{extension}"))
     } else {
-        PathBuf::from(format!("{stem}.map"))
+        PathBuf::from(format!("{stem}.{extension}"))
     };
     if out.as_os_str().is_empty() {
-        out = PathBuf::from("a.map");
+        out = PathBuf::from(format!("a.{extension}"));
     }
     Some(out)
 }
@@ -519,9 +1117,58 @@ fn validate_unresolved_symbols(summaries: &[ObjectSummary], input_names: &[Strin
     anyhow::bail!("{}", messages.join("\n"));
 }
 
+/// Rejects non-common symbols defined by more than one object.
+///
+/// With `--allow-multiple-definition` this check is skipped and the first
+/// definition in link order wins, matching archive member selection. `-d`
+/// definitions have already replaced the objects' ones and never conflict.
+fn validate_multiple_definitions(
+    summaries: &[ObjectSummary],
+    input_names: &[String],
+) -> anyhow::Result<()> {
+    let mut owners = HashMap::<&[u8], usize>::new();
+    let mut messages = Vec::<String>::new();
+    for (idx, s) in summaries.iter().enumerate() {
+        for sym in &s.symbols {
+            if sym.section.is_common() || sym.name.first() == Some(&b'*') {
+                continue;
+            }
+            let owner = *owners.entry(sym.name.as_slice()).or_insert(idx);
+            if owner == idx {
+                continue;
+            }
+            let name = String::from_utf8_lossy(&sym.name);
+            let first = input_names.get(owner).map_or("<unknown>", String::as_str);
+            let second = input_names.get(idx).map_or("<unknown>", String::as_str);
+            messages.push(format!("シンボルが二重定義されています: {name} in {first} and {second}"));
+        }
+    }
+    if messages.is_empty() {
+        return Ok(());
+    }
+    anyhow::bail!("{}", messages.join("\n"));
+}
+
+#[derive(Debug, Default)]
+struct LoadContext {
+    verbose: bool,
+    trace_symbols: Vec<Vec<u8>>,
+    wraps: Vec<Vec<u8>>,
+    /// Objects placed ahead of the inputs so archive selection sees their definitions.
+    preloaded: Vec<(String, ObjectFile, ObjectSummary)>,
+    extractions: Vec<ArchiveExtraction>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct ArchiveExtraction {
+    reference: String,
+    extracted: String,
+    symbol: Vec<u8>,
+}
+
 fn load_objects_with_requests_paths(
     initial_inputs: &[PathBuf],
-    verbose: bool,
+    ctx: &mut LoadContext,
 ) -> anyhow::Result<(Vec<crate::format::obj::ObjectFile>, Vec<ObjectSummary>, Vec<String>)> {
     const MAX_ARCHIVE_VISITS: usize = 64;
     let mut objects = Vec::new();
@@ -532,6 +1179,11 @@ fn load_objects_with_requests_paths(
     let mut archive_visits = HashMap::<PathBuf, usize>::new();
 
     pending.extend(initial_inputs.iter().cloned());
+    for (label, object, summary) in std::mem::take(&mut ctx.preloaded) {
+        objects.push(object);
+        summaries.push(summary);
+        input_names.push(label);
+    }
 
     while let Some(path) = pending.pop_front() {
         let abs = absolutize_path(&path)?;
@@ -559,12 +1211,14 @@ fn load_objects_with_requests_paths(
             let name = display_name(&path);
             anyhow::anyhow!("ファイルがありません: {name}")
         })?;
-        match parse_object(&bytes) {
+        match parse_object_at(&bytes) {
             Ok(object) => {
-                let summary = resolve_object(&object);
+                let mut summary = resolve_object(&object);
+                apply_wraps(&mut summary, &ctx.wraps);
                 let label = path.to_string_lossy().to_string();
                 let mut state = LoadState {
-                    verbose,
+                    verbose: ctx.verbose,
+                    trace_symbols: &ctx.trace_symbols,
                     pending: &mut pending,
                     base_dir: abs.parent().unwrap_or(Path::new(".")),
                     objects: &mut objects,
@@ -573,7 +1227,7 @@ fn load_objects_with_requests_paths(
                 };
                 state.add_loaded_object(label, object, summary)?;
             }
-            Err(FormatError::UnsupportedCommand(_)) if archive_like && is_ar_archive(&bytes) => {
+            Err(e) if matches!(e.kind, FormatError::UnsupportedCommand(_)) && archive_like && is_ar_archive(&bytes) => {
                 let members = parse_ar_members(&bytes)?;
                 if members.is_empty() {
                     let name = display_name(&path);
@@ -582,21 +1236,19 @@ fn load_objects_with_requests_paths(
                 let base_dir = abs.parent().unwrap_or(Path::new("."));
                 let mut parsed_members = Vec::new();
                 for (member_name, payload) in members {
-                    let object = parse_object(&payload).map_err(|e| {
+                    let object = parse_object_at(&payload).map_err(|e| {
                         anyhow::anyhow!("{}({}): {}", path.to_string_lossy(), member_name, e)
                     })?;
-                    let summary = resolve_object(&object);
+                    let mut summary = resolve_object(&object);
+                    apply_wraps(&mut summary, &ctx.wraps);
                     parsed_members.push((member_name, object, summary));
                 }
-                let select_indices = select_archive_members(&summaries, &parsed_members);
-                let mut selected = vec![false; parsed_members.len()];
-                for idx in select_indices {
-                    if let Some(slot) = selected.get_mut(idx) {
-                        *slot = true;
-                    }
-                }
+                let selections = select_archive_members(&summaries, &parsed_members);
+                let selected =
+                    record_extractions(ctx, &path, &parsed_members, &selections, &input_names);
                 let mut state = LoadState {
-                    verbose,
+                    verbose: ctx.verbose,
+                    trace_symbols: &ctx.trace_symbols,
                     pending: &mut pending,
                     base_dir,
                     objects: &mut objects,
@@ -621,8 +1273,35 @@ fn load_objects_with_requests_paths(
     Ok((objects, summaries, input_names))
 }
 
+fn record_extractions(
+    ctx: &mut LoadContext,
+    archive: &Path,
+    members: &[(String, crate::format::obj::ObjectFile, ObjectSummary)],
+    selections: &[ArchiveSelection],
+    input_names: &[String],
+) -> Vec<bool> {
+    let member_label = |idx: usize| format!("{}({})", archive.to_string_lossy(), members[idx].0);
+    let mut selected = vec![false; members.len()];
+    for sel in selections {
+        let reference = match sel.referrer {
+            Referrer::Loaded(i) => input_names[i].clone(),
+            Referrer::Member(i) => member_label(i),
+        };
+        ctx.extractions.push(ArchiveExtraction {
+            reference,
+            extracted: member_label(sel.index),
+            symbol: sel.symbol.clone(),
+        });
+        if let Some(slot) = selected.get_mut(sel.index) {
+            *slot = true;
+        }
+    }
+    selected
+}
+
 struct LoadState<'a> {
     verbose: bool,
+    trace_symbols: &'a [Vec<u8>],
     pending: &'a mut VecDeque<PathBuf>,
     base_dir: &'a Path,
     objects: &'a mut Vec<crate::format::obj::ObjectFile>,
@@ -649,6 +1328,20 @@ impl LoadState<'_> {
                 summary.requests.len()
             );
         }
+        for name in self.trace_symbols {
+            let shown = String::from_utf8_lossy(name);
+            for sym in summary.symbols.iter().filter(|sym| &sym.name == name) {
+                let kind = if sym.section.is_common() {
+                    "common definition"
+                } else {
+                    "definition"
+                };
+                println!("{label}: {kind} of {shown}");
+            }
+            if summary.xrefs.iter().any(|xr| &xr.name == name) {
+                println!("{label}: reference to {shown}");
+            }
+        }
         enqueue_requests(self.pending, self.base_dir, &summary.requests)?;
         self.objects.push(object);
         self.summaries.push(summary);
@@ -793,11 +1486,24 @@ fn parse_ar_member_header(bytes: &[u8], pos: &mut usize) -> anyhow::Result<ArMem
     Ok(ArMemberHeader { raw_name, size })
 }
 
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct ArchiveSelection {
+    index: usize,
+    symbol: Vec<u8>,
+    referrer: Referrer,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Referrer {
+    Loaded(usize),
+    Member(usize),
+}
+
 fn select_archive_members(
     loaded_summaries: &[ObjectSummary],
     members: &[(String, crate::format::obj::ObjectFile, ObjectSummary)],
-) -> Vec<usize> {
-    let mut selected = Vec::<usize>::new();
+) -> Vec<ArchiveSelection> {
+    let mut selected = Vec::<ArchiveSelection>::new();
     let mut selected_mask = vec![false; members.len()];
     let (mut defs, mut unresolved) = build_symbol_sets(loaded_summaries);
     if unresolved.is_empty() {
@@ -810,18 +1516,19 @@ fn select_archive_members(
             if selected_mask[idx] {
                 continue;
             }
-            let provides_needed = sum.symbols.iter().any(|s| unresolved.contains(&s.name));
-            if !provides_needed {
+            let Some((symbol, referrer)) = sum
+                .symbols
+                .iter()
+                .find_map(|s| unresolved.get(&s.name).map(|r| (s.name.clone(), *r)))
+            else {
                 continue;
-            }
-            select_member(
-                idx,
-                &mut selected,
-                &mut selected_mask,
-                &mut defs,
-                &mut unresolved,
-                sum,
-            );
+            };
+            selected.push(ArchiveSelection {
+                index: idx,
+                symbol,
+                referrer,
+            });
+            select_member(idx, &mut selected_mask, &mut defs, &mut unresolved, sum);
             changed = true;
         }
         if !changed {
@@ -831,31 +1538,31 @@ fn select_archive_members(
     selected
 }
 
-fn build_symbol_sets(loaded_summaries: &[ObjectSummary]) -> (HashSet<Vec<u8>>, HashSet<Vec<u8>>) {
+fn build_symbol_sets(
+    loaded_summaries: &[ObjectSummary],
+) -> (HashSet<Vec<u8>>, HashMap<Vec<u8>, Referrer>) {
     let mut defs = HashSet::<Vec<u8>>::new();
-    let mut unresolved = HashSet::<Vec<u8>>::new();
+    let mut unresolved = HashMap::<Vec<u8>, Referrer>::new();
     for sum in loaded_summaries {
         add_defined_symbols(&mut defs, &sum.symbols);
     }
-    for sum in loaded_summaries {
-        extend_unresolved(&mut unresolved, &defs, &sum.xrefs);
+    for (idx, sum) in loaded_summaries.iter().enumerate() {
+        extend_unresolved(&mut unresolved, &defs, &sum.xrefs, Referrer::Loaded(idx));
     }
     (defs, unresolved)
 }
 
 fn select_member(
     idx: usize,
-    selected: &mut Vec<usize>,
     selected_mask: &mut [bool],
     defs: &mut HashSet<Vec<u8>>,
-    unresolved: &mut HashSet<Vec<u8>>,
+    unresolved: &mut HashMap<Vec<u8>, Referrer>,
     summary: &ObjectSummary,
 ) {
-    selected.push(idx);
     selected_mask[idx] = true;
     add_defined_symbols(defs, &summary.symbols);
-    extend_unresolved(unresolved, defs, &summary.xrefs);
-    unresolved.retain(|name| !defs.contains(name));
+    extend_unresolved(unresolved, defs, &summary.xrefs, Referrer::Member(idx));
+    unresolved.retain(|name, _| !defs.contains(name));
 }
 
 fn add_defined_symbols(defs: &mut HashSet<Vec<u8>>, symbols: &[crate::resolver::Symbol]) {
@@ -863,16 +1570,14 @@ fn add_defined_symbols(defs: &mut HashSet<Vec<u8>>, symbols: &[crate::resolver::
 }
 
 fn extend_unresolved(
-    unresolved: &mut HashSet<Vec<u8>>,
+    unresolved: &mut HashMap<Vec<u8>, Referrer>,
     defs: &HashSet<Vec<u8>>,
     xrefs: &[crate::resolver::Symbol],
+    referrer: Referrer,
 ) {
-    unresolved.extend(
-        xrefs
-            .iter()
-            .filter(|xr| !defs.contains(&xr.name))
-            .map(|xr| xr.name.clone()),
-    );
+    for xr in xrefs.iter().filter(|xr| !defs.contains(&xr.name)) {
+        unresolved.entry(xr.name.clone()).or_insert(referrer);
+    }
 }
 
 fn trim_member_name(name: &str) -> String {
@@ -902,15 +1607,24 @@ fn resolve_gnu_long_name(table: Option<&[u8]>, offset: usize) -> Option<String>
 #[cfg(test)]
 mod tests {
     use super::{
-        inject_define_symbols, inject_section_info_object, is_ar_archive, load_objects_with_requests_paths,
-        parse_ar_members, resolve_lib_inputs, resolve_map_output, resolve_output_path, run,
-        select_archive_members,
-        update_section_info_rsize, validate_unresolved_symbols,
+        apply_entry_override, apply_scd_mode, validate_target_layout, define_symbols_object, inject_stack_object, inject_overlay_placeholders, inject_section_info_object,
+        is_ar_archive, prune_symbol_file_definitions, read_symbol_files, symbol_file_object, link_overlays, load_just_symbols,
+        load_objects_with_requests_paths, load_overlay_groups, parse_ar_members, prepare_objects,
+        prune_just_symbols, resolve_define_symbols, resolve_debug_output, resolve_lib_inputs, resolve_map_output,
+        resolve_output_path, run, select_archive_members, size_budgets, target_cpu, validate_args,
+        validate_overlay_references, LoadContext, Referrer, update_section_info_rsize, validate_multiple_definitions,
+        validate_start_address_uniqueness, validate_unresolved_symbols,
+    };
+    use crate::cli::{
+        Args, CpuModel, DefineArg, DefineExpr, EntryArg, JustSymbolsArg, MaxSizeArg, OverlayArg, ScdMode,
+        SizeReportMode,
     };
-    use crate::cli::{Args, DefineArg};
+    use clap::Parser;
     use crate::layout::plan_layout;
+    use crate::writer::symbol_addresses;
     use crate::format::obj::{Command, ObjectFile, parse_object};
     use crate::resolver::{SectionKind, resolve_object};
+    use crate::target::Cpu;
     use std::fs;
     use std::path::{Path, PathBuf};
     use std::time::{SystemTime, UNIX_EPOCH};
@@ -1074,7 +1788,7 @@ mod tests {
         fs::write(&sub, [0x00, 0x00]).expect("write sub");
 
         let (_, _, names) =
-            load_objects_with_requests_paths(std::slice::from_ref(&main), false).expect("load");
+            load_objects_with_requests_paths(std::slice::from_ref(&main), &mut LoadContext::default()).expect("load");
         assert_eq!(names.len(), 2);
         assert_eq!(names[0], main.to_string_lossy());
         assert!(names.iter().any(|v| v.ends_with("sub.o")));
@@ -1096,7 +1810,7 @@ mod tests {
         let main = dir.join("main.o");
         fs::write(&main, [0xe0, 0x01, b'n', b'o', b'n', b'e', b'.', b'o', 0x00, 0x00]).expect("write main");
 
-        let err = load_objects_with_requests_paths(std::slice::from_ref(&main), false)
+        let err = load_objects_with_requests_paths(std::slice::from_ref(&main), &mut LoadContext::default())
             .expect_err("must fail");
         assert!(err.to_string().contains("ファイルがありません: none.o"));
 
@@ -1121,7 +1835,7 @@ mod tests {
         fs::write(&sub, [0x00, 0x00]).expect("write sub");
 
         let (_, _, names) =
-            load_objects_with_requests_paths(std::slice::from_ref(&main), false).expect("load");
+            load_objects_with_requests_paths(std::slice::from_ref(&main), &mut LoadContext::default()).expect("load");
         assert_eq!(names.len(), 2);
         assert!(names.iter().any(|v| v.ends_with("sub.o")));
 
@@ -1146,7 +1860,7 @@ mod tests {
         fs::write(&lib, ar).expect("write lib");
 
         let (_, sums, names) =
-            load_objects_with_requests_paths(std::slice::from_ref(&main), false).expect("load");
+            load_objects_with_requests_paths(std::slice::from_ref(&main), &mut LoadContext::default()).expect("load");
         validate_unresolved_symbols(&sums, &names).expect("resolved");
         assert!(names.iter().any(|v| v.ends_with("libx.a(foo.o)")));
 
@@ -1169,6 +1883,7 @@ mod tests {
             output: None,
             r_format: false,
             r_no_check: false,
+            self_relocate: false,
             no_x_ext: false,
             opt_an: false,
             align: None,
@@ -1182,6 +1897,9 @@ mod tests {
             g2lk_off: false,
             g2lk_on: false,
             make_mcs: false,
+            make_device: false,
+            z_format: false,
+            make_prg: false,
             omit_bss: false,
             cut_symbols: false,
             map: None,
@@ -1190,6 +1908,29 @@ mod tests {
             warn_off: false,
             title: false,
             section_info: false,
+            size_report: None,
+            size_format: SizeReportMode::Text,
+            size_diff: Vec::new(),
+            why_extract: None,
+            trace_symbols: Vec::new(),
+            allow_multiple_definition: false,
+            wraps: Vec::new(),
+            symbol_files: Vec::new(),
+            just_symbols: Vec::new(),
+            branch_islands: false,
+            target_check: false,
+            scd: ScdMode::Lenient,
+            strip_debug: false,
+            strip_all: false,
+            keep_symbols: Vec::new(),
+            strip_symbols: Vec::new(),
+            debug_file: None,
+            cpu: None,
+            max_sizes: Vec::new(),
+            print_memory_usage: false,
+            stack_size: None,
+            entry: None,
+            overlays: Vec::new(),
             inputs: vec![],
         };
         let libs = resolve_lib_inputs(&args).expect("resolve");
@@ -1221,7 +1962,7 @@ mod tests {
         let prev = std::env::current_dir().expect("cwd");
         std::env::set_current_dir(&dir_cwd).expect("chdir");
         let (_, _, names) =
-            load_objects_with_requests_paths(std::slice::from_ref(&main), false).expect("load");
+            load_objects_with_requests_paths(std::slice::from_ref(&main), &mut LoadContext::default()).expect("load");
         std::env::set_current_dir(prev).expect("restore cwd");
 
         assert_eq!(names.len(), 2);
@@ -1251,7 +1992,7 @@ mod tests {
         fs::write(&lib, ar).expect("write lib");
 
         let (_, _, names) =
-            load_objects_with_requests_paths(std::slice::from_ref(&main), false).expect("must load");
+            load_objects_with_requests_paths(std::slice::from_ref(&main), &mut LoadContext::default()).expect("must load");
         assert_eq!(names.len(), 2);
         assert!(names.iter().any(|v| v.ends_with("libx.a(foo.o)")));
         assert!(!names.iter().any(|v| v.ends_with("libx.a(bar.o)")));
@@ -1261,6 +2002,85 @@ mod tests {
         let _ = fs::remove_dir(dir);
     }
 
+    #[test]
+    fn records_why_archive_members_were_extracted() {
+        let uniq = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .expect("time")
+            .as_nanos();
+        let dir = std::env::temp_dir().join(format!("rhlk-linker-why-{uniq}"));
+        fs::create_dir_all(&dir).expect("mkdir");
+
+        let main = dir.join("main.o");
+        let lib = dir.join("libx.a");
+        fs::write(&main, obj_with_xref_and_request("foo", "libx.a")).expect("write main");
+        let ar = make_simple_ar(&[
+            ("bar.o", &obj_with_def("bar")),
+            ("foo.o", &obj_with_def_and_xref("foo", "bar")),
+        ]);
+        fs::write(&lib, ar).expect("write lib");
+
+        let mut ctx = LoadContext::default();
+        load_objects_with_requests_paths(std::slice::from_ref(&main), &mut ctx).expect("must load");
+        let why = ctx
+            .extractions
+            .iter()
+            .map(|e| {
+                (
+                    e.reference.rsplit('/').next().unwrap_or_default().to_string(),
+                    e.extracted.rsplit('/').next().unwrap_or_default().to_string(),
+                    e.symbol.clone(),
+                )
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(
+            why,
+            vec![
+                ("main.o".to_string(), "libx.a(foo.o)".to_string(), b"foo".to_vec()),
+                ("libx.a(foo.o)".to_string(), "libx.a(bar.o)".to_string(), b"bar".to_vec()),
+            ]
+        );
+
+        let _ = fs::remove_file(main);
+        let _ = fs::remove_file(lib);
+        let _ = fs::remove_dir(dir);
+    }
+
+    #[test]
+    fn wrap_redirects_archive_selection_and_real_reference() {
+        let uniq = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .expect("time")
+            .as_nanos();
+        let dir = std::env::temp_dir().join(format!("rhlk-linker-wrap-{uniq}"));
+        fs::create_dir_all(&dir).expect("mkdir");
+
+        let main = dir.join("main.o");
+        let lib = dir.join("libx.a");
+        fs::write(&main, obj_with_xref_and_request("foo", "libx.a")).expect("write main");
+        let ar = make_simple_ar(&[
+            ("foo.o", &obj_with_def("foo")),
+            ("wrap.o", &obj_with_def_and_xref("__wrap_foo", "__real_foo")),
+        ]);
+        fs::write(&lib, ar).expect("write lib");
+
+        let mut ctx = LoadContext {
+            wraps: vec![b"foo".to_vec()],
+            ..LoadContext::default()
+        };
+        let (_, sums, names) =
+            load_objects_with_requests_paths(std::slice::from_ref(&main), &mut ctx).expect("must load");
+        assert_eq!(sums[0].xrefs[0].name, b"__wrap_foo".to_vec());
+        assert_eq!(names.len(), 3);
+        assert!(names.iter().any(|v| v.ends_with("libx.a(wrap.o)")));
+        assert!(names.iter().any(|v| v.ends_with("libx.a(foo.o)")));
+        validate_unresolved_symbols(&sums, &names).expect("all resolved");
+
+        let _ = fs::remove_file(main);
+        let _ = fs::remove_file(lib);
+        let _ = fs::remove_dir(dir);
+    }
+
     #[test]
     fn parses_simple_ar_members() {
         let ar = make_simple_ar(&[("x.o", &[0x00, 0x00]), ("y.o", &[0x10, 0x00, 0x00, 0x00])]);
@@ -1328,6 +2148,7 @@ mod tests {
             ("bar.o".to_string(), bar_obj.clone(), bar_sum.clone()),
         ];
         let picked = select_archive_members(&[main_sum], &members);
+        let picked = picked.iter().map(|sel| sel.index).collect::<Vec<_>>();
         assert_eq!(picked, vec![0]);
     }
 
@@ -1347,6 +2168,7 @@ mod tests {
             ("bar.o".to_string(), bar_obj, bar_sum),
         ];
         let picked = select_archive_members(&[main_sum], &members);
+        let picked = picked.iter().map(|sel| sel.index).collect::<Vec<_>>();
         assert_eq!(picked, vec![0, 1]);
     }
 
@@ -1367,9 +2189,30 @@ mod tests {
             ("foo.o".to_string(), foo_obj, foo_sum),
         ];
         let picked = select_archive_members(&[main_sum], &members);
+        let picked = picked.iter().map(|sel| sel.index).collect::<Vec<_>>();
         assert_eq!(picked, vec![1, 0]);
     }
 
+    #[test]
+    fn selection_records_trigger_symbol_and_referrer() {
+        let main = parse_object(&obj_with_xref_and_request("foo", "libx.a")).expect("main parse");
+        let main_sum = resolve_object(&main);
+        let foo_obj = parse_object(&obj_with_def_and_xref("foo", "bar")).expect("foo parse");
+        let foo_sum = resolve_object(&foo_obj);
+        let bar_obj = parse_object(&obj_with_def("bar")).expect("bar parse");
+        let bar_sum = resolve_object(&bar_obj);
+
+        let members = vec![
+            ("foo.o".to_string(), foo_obj, foo_sum),
+            ("bar.o".to_string(), bar_obj, bar_sum),
+        ];
+        let picked = select_archive_members(&[main_sum], &members);
+        assert_eq!(picked[0].symbol, b"foo".to_vec());
+        assert_eq!(picked[0].referrer, Referrer::Loaded(0));
+        assert_eq!(picked[1].symbol, b"bar".to_vec());
+        assert_eq!(picked[1].referrer, Referrer::Member(0));
+    }
+
     #[test]
     fn prefers_first_member_for_duplicate_definition() {
         let main_bytes = obj_with_xref_and_request("foo", "libx.a");
@@ -1386,9 +2229,70 @@ mod tests {
             ("foo2.o".to_string(), foo2_obj, foo2_sum),
         ];
         let picked = select_archive_members(&[main_sum], &members);
+        let picked = picked.iter().map(|sel| sel.index).collect::<Vec<_>>();
         assert_eq!(picked, vec![0]);
     }
 
+    #[test]
+    fn reports_multiple_definitions_with_both_objects() {
+        let a = resolve_object(&parse_object(&obj_with_def("foo")).expect("a parse"));
+        let b = resolve_object(&parse_object(&obj_with_def("foo")).expect("b parse"));
+        let inputs = vec!["a.o".to_string(), "libx.a(b.o)".to_string()];
+        let err = validate_multiple_definitions(&[a, b], &inputs).expect_err("must fail");
+        assert!(err
+            .to_string()
+            .contains("シンボルが二重定義されています: foo in a.o and libx.a(b.o)"));
+    }
+
+    #[test]
+    fn entry_override_picks_one_of_several_start_addresses() {
+        let mut a = resolve_object(&parse_object(&obj_with_def("_start_a")).expect("a parse"));
+        let mut b = resolve_object(&parse_object(&obj_with_def("_start_b")).expect("b parse"));
+        for sum in [&mut a, &mut b] {
+            sum.declared_section_sizes.insert(SectionKind::Text, 4);
+            sum.start_address = Some((0x01, 0));
+        }
+        let inputs = vec!["a.o".to_string(), "b.o".to_string()];
+        assert!(validate_start_address_uniqueness(&[a.clone(), b.clone()], &inputs).is_err());
+
+        let mut summaries = vec![a, b];
+        let layout = plan_layout(&summaries);
+        apply_entry_override(&EntryArg::Symbol("_start_b".to_string()), &mut summaries, &layout, 0)
+            .expect("entry");
+        assert_eq!(summaries[0].start_address, Some((0x01, 4)));
+        assert_eq!(summaries[1].start_address, None);
+
+        apply_entry_override(&EntryArg::Address(2), &mut summaries, &layout, 0).expect("address");
+        assert_eq!(summaries[0].start_address, Some((0x01, 2)));
+        let err = apply_entry_override(&EntryArg::Symbol("_missing".to_string()), &mut summaries, &layout, 0)
+            .expect_err("undefined");
+        assert!(err.to_string().contains("未定義シンボル: _missing in --entry"));
+        assert!(apply_entry_override(&EntryArg::Address(3), &mut summaries, &layout, 0).is_err());
+
+        // With -b the address is absolute; the symbol form is unaffected.
+        let args = Args::parse_from(["rhlk", "-b", "0x10000", "--entry", "0x10006", "a.o"]);
+        let base = args.runtime_config().base_address;
+        let entry = args.entry.expect("entry");
+        apply_entry_override(&entry, &mut summaries, &layout, base).expect("absolute address");
+        assert_eq!(summaries[0].start_address, Some((0x01, 6)));
+        let err = apply_entry_override(&EntryArg::Address(2), &mut summaries, &layout, base).expect_err("below base");
+        assert_eq!(err.to_string(), "entry address 0x2 is below the base address 0x10000");
+        apply_entry_override(&EntryArg::Symbol("_start_b".to_string()), &mut summaries, &layout, base)
+            .expect("entry");
+        assert_eq!(summaries[0].start_address, Some((0x01, 4)));
+    }
+
+    #[test]
+    fn common_symbols_are_not_multiple_definitions() {
+        let mut a = resolve_object(&parse_object(&obj_with_def("foo")).expect("a parse"));
+        let mut b = a.clone();
+        a.symbols[0].section = SectionKind::Common;
+        b.symbols[0].section = SectionKind::Common;
+        let c = resolve_object(&parse_object(&obj_with_def("foo")).expect("c parse"));
+        let inputs = vec!["a.o".to_string(), "b.o".to_string(), "c.o".to_string()];
+        validate_multiple_definitions(&[a, b, c], &inputs).expect("commons merge");
+    }
+
     #[test]
     fn reports_unresolved_symbols_after_expansion() {
         let main = parse_object(&obj_with_xref_and_request("foo", "libx.a")).expect("main parse");
@@ -1418,7 +2322,7 @@ mod tests {
             main.clone(),
             lib.clone(),
         ];
-        let (_, sums, names) = load_objects_with_requests_paths(&inputs, false).expect("load");
+        let (_, sums, names) = load_objects_with_requests_paths(&inputs, &mut LoadContext::default()).expect("load");
         validate_unresolved_symbols(&sums, &names).expect("resolved");
         assert!(names.iter().any(|v| v.ends_with("libx.a(foo.o)")));
 
@@ -1448,7 +2352,7 @@ mod tests {
             main1.clone(),
             main2.clone(),
         ];
-        let (_, sums, names) = load_objects_with_requests_paths(&inputs, false).expect("load");
+        let (_, sums, names) = load_objects_with_requests_paths(&inputs, &mut LoadContext::default()).expect("load");
         // dummy stays unresolved (expected), but foo should be resolved by second archive visit.
         let err = validate_unresolved_symbols(&sums, &names).expect_err("must have unresolved");
         assert!(!err.to_string().contains("未定義シンボル: foo"));
@@ -1459,6 +2363,30 @@ mod tests {
         let _ = fs::remove_dir(dir);
     }
 
+    #[test]
+    fn memory_map_is_checked_only_with_target_check() {
+        let obj = ObjectFile {
+            commands: vec![
+                Command::Header {
+                    section: 0x01,
+                    size: 0x100,
+                    name: b"text".to_vec(),
+                },
+                Command::End,
+            ],
+            scd_tail: Vec::new(),
+        };
+        let layout = plan_layout(&[resolve_object(&obj)]);
+        for base in [0, 0x00ed_0100, 0x0100_0000] {
+            validate_target_layout(Some(base), &layout, false).expect("unchecked base");
+        }
+        let err = validate_target_layout(Some(0), &layout, true).expect_err("vectors");
+        assert!(err.to_string().contains("overlaps exception vectors"));
+        validate_target_layout(Some(0x6800), &layout, true).expect("ram");
+        let err = validate_target_layout(Some(0xffff_ffff), &layout, false).expect_err("overflow");
+        assert!(err.to_string().contains("overflows the 32-bit address space"));
+    }
+
     #[test]
     fn resolves_map_output_name() {
         let o = resolve_map_output(Some(""), Some(Path::new("out.x")), &[PathBuf::from("in.o")]);
@@ -1471,12 +2399,22 @@ mod tests {
         assert_eq!(e, Some(PathBuf::from("foo.txt")));
     }
 
+    #[test]
+    fn resolves_debug_output_name() {
+        let out = Path::new("rom/boot.r");
+        assert_eq!(resolve_debug_output(Some(""), out), Some(PathBuf::from("rom/boot.dbg")));
+        assert_eq!(resolve_debug_output(Some("boot"), out), Some(PathBuf::from("boot.dbg")));
+        assert_eq!(resolve_debug_output(Some("boot.sym"), out), Some(PathBuf::from("boot.sym")));
+        assert_eq!(resolve_debug_output(None, out), None);
+    }
+
     #[test]
     fn resolves_output_path_name() {
         let mut args = Args {
             output: None,
             r_format: false,
             r_no_check: false,
+            self_relocate: false,
             no_x_ext: false,
             opt_an: false,
             align: None,
@@ -1484,6 +2422,9 @@ mod tests {
             load_mode: None,
             defines: Vec::new(),
             make_mcs: false,
+            make_device: false,
+            z_format: false,
+            make_prg: false,
             omit_bss: false,
             cut_symbols: false,
             map: None,
@@ -1492,6 +2433,29 @@ mod tests {
             warn_off: false,
             title: false,
             section_info: false,
+            size_report: None,
+            size_format: SizeReportMode::Text,
+            size_diff: Vec::new(),
+            why_extract: None,
+            trace_symbols: Vec::new(),
+            allow_multiple_definition: false,
+            wraps: Vec::new(),
+            symbol_files: Vec::new(),
+            just_symbols: Vec::new(),
+            branch_islands: false,
+            target_check: false,
+            scd: ScdMode::Lenient,
+            strip_debug: false,
+            strip_all: false,
+            keep_symbols: Vec::new(),
+            strip_symbols: Vec::new(),
+            debug_file: None,
+            cpu: None,
+            max_sizes: Vec::new(),
+            print_memory_usage: false,
+            stack_size: None,
+            entry: None,
+            overlays: Vec::new(),
             indirect_files: Vec::new(),
             lib_paths: Vec::new(),
             libs: Vec::new(),
@@ -1534,6 +2498,7 @@ mod tests {
             output: None,
             r_format: false,
             r_no_check: false,
+            self_relocate: false,
             no_x_ext: false,
             opt_an: false,
             align: None,
@@ -1541,6 +2506,9 @@ mod tests {
             load_mode: None,
             defines: Vec::new(),
             make_mcs: false,
+            make_device: false,
+            z_format: false,
+            make_prg: false,
             omit_bss: false,
             cut_symbols: false,
             map: Some(String::new()),
@@ -1549,6 +2517,29 @@ mod tests {
             warn_off: false,
             title: false,
             section_info: false,
+            size_report: None,
+            size_format: SizeReportMode::Text,
+            size_diff: Vec::new(),
+            why_extract: None,
+            trace_symbols: Vec::new(),
+            allow_multiple_definition: false,
+            wraps: Vec::new(),
+            symbol_files: Vec::new(),
+            just_symbols: Vec::new(),
+            branch_islands: false,
+            target_check: false,
+            scd: ScdMode::Lenient,
+            strip_debug: false,
+            strip_all: false,
+            keep_symbols: Vec::new(),
+            strip_symbols: Vec::new(),
+            debug_file: None,
+            cpu: None,
+            max_sizes: Vec::new(),
+            print_memory_usage: false,
+            stack_size: None,
+            entry: None,
+            overlays: Vec::new(),
             indirect_files: Vec::new(),
             lib_paths: Vec::new(),
             libs: Vec::new(),
@@ -1581,6 +2572,7 @@ mod tests {
             output: None,
             r_format: false,
             r_no_check: false,
+            self_relocate: false,
             no_x_ext: false,
             opt_an: false,
             align: None,
@@ -1588,6 +2580,9 @@ mod tests {
             load_mode: None,
             defines: Vec::new(),
             make_mcs: false,
+            make_device: false,
+            z_format: false,
+            make_prg: false,
             omit_bss: false,
             cut_symbols: false,
             map: Some(map_path.to_string_lossy().to_string()),
@@ -1596,6 +2591,29 @@ mod tests {
             warn_off: false,
             title: false,
             section_info: false,
+            size_report: None,
+            size_format: SizeReportMode::Text,
+            size_diff: Vec::new(),
+            why_extract: None,
+            trace_symbols: Vec::new(),
+            allow_multiple_definition: false,
+            wraps: Vec::new(),
+            symbol_files: Vec::new(),
+            just_symbols: Vec::new(),
+            branch_islands: false,
+            target_check: false,
+            scd: ScdMode::Lenient,
+            strip_debug: false,
+            strip_all: false,
+            keep_symbols: Vec::new(),
+            strip_symbols: Vec::new(),
+            debug_file: None,
+            cpu: None,
+            max_sizes: Vec::new(),
+            print_memory_usage: false,
+            stack_size: None,
+            entry: None,
+            overlays: Vec::new(),
             indirect_files: Vec::new(),
             lib_paths: Vec::new(),
             libs: Vec::new(),
@@ -1612,12 +2630,33 @@ mod tests {
         let _ = fs::remove_dir(dir);
     }
 
+    #[test]
+    fn overlays_require_an_x_or_fixed_address_root() {
+        let with = |extra: &[&str]| {
+            let mut argv = vec!["rhlk", "--overlay", "ov.r=ov.o"];
+            argv.extend_from_slice(extra);
+            argv.push("main.o");
+            validate_args(&Args::parse_from(argv))
+        };
+        let err = with(&["-b", "0x10000", "-r", "--self-relocate"]).expect_err("self-relocating root");
+        assert_eq!(
+            err.to_string(),
+            "--overlay requires an .x root or a fixed-address root (--z-format or -r)"
+        );
+        assert!(with(&["-r"]).is_err());
+        assert!(with(&["-b", "0x10000"]).is_err());
+        with(&[]).expect(".x root");
+        with(&["-b", "0x10000", "-r"]).expect("fixed .r root");
+        with(&["-b", "0x10000", "--z-format"]).expect("z root");
+    }
+
     #[test]
     fn rejects_invalid_align_option_value() {
         let args = Args {
             output: None,
             r_format: false,
             r_no_check: false,
+            self_relocate: false,
             no_x_ext: false,
             opt_an: false,
             align: Some(3),
@@ -1625,6 +2664,9 @@ mod tests {
             load_mode: None,
             defines: Vec::new(),
             make_mcs: false,
+            make_device: false,
+            z_format: false,
+            make_prg: false,
             omit_bss: false,
             cut_symbols: false,
             map: None,
@@ -1633,6 +2675,29 @@ mod tests {
             warn_off: false,
             title: false,
             section_info: false,
+            size_report: None,
+            size_format: SizeReportMode::Text,
+            size_diff: Vec::new(),
+            why_extract: None,
+            trace_symbols: Vec::new(),
+            allow_multiple_definition: false,
+            wraps: Vec::new(),
+            symbol_files: Vec::new(),
+            just_symbols: Vec::new(),
+            branch_islands: false,
+            target_check: false,
+            scd: ScdMode::Lenient,
+            strip_debug: false,
+            strip_all: false,
+            keep_symbols: Vec::new(),
+            strip_symbols: Vec::new(),
+            debug_file: None,
+            cpu: None,
+            max_sizes: Vec::new(),
+            print_memory_usage: false,
+            stack_size: None,
+            entry: None,
+            overlays: Vec::new(),
             indirect_files: Vec::new(),
             lib_paths: Vec::new(),
             libs: Vec::new(),
@@ -1647,46 +2712,84 @@ mod tests {
 
     #[test]
     fn injects_define_symbols_as_absolute_xdef() {
-        let args = Args {
-            output: None,
-            r_format: false,
-            r_no_check: false,
-            no_x_ext: false,
-            opt_an: false,
-            align: None,
-            base_address: None,
-            load_mode: None,
-            defines: vec![DefineArg {
-                name: "_FOO".to_string(),
-                value: 0x1234,
-            }],
-            make_mcs: false,
-            omit_bss: false,
-            cut_symbols: false,
-            map: None,
-            verbose: false,
-            quiet: false,
-            warn_off: false,
-            title: false,
-            section_info: false,
-            indirect_files: Vec::new(),
-            lib_paths: Vec::new(),
-            libs: Vec::new(),
-            use_env_lib: false,
-            g2lk_off: false,
-            g2lk_on: false,
-            inputs: vec![PathBuf::from("in.o")],
-        };
-        let mut objects = Vec::new();
-        let mut summaries = Vec::new();
-        let mut names = Vec::new();
-        inject_define_symbols(&args, &mut objects, &mut summaries, &mut names);
-        assert_eq!(objects.len(), 1);
-        assert_eq!(summaries.len(), 1);
-        assert_eq!(names, vec!["*DEFINE*"]);
-        assert_eq!(summaries[0].symbols.len(), 1);
-        assert_eq!(summaries[0].symbols[0].name, b"_FOO".to_vec());
-        assert_eq!(summaries[0].symbols[0].value, 0x1234);
+        let (name, _, summary) = define_symbols_object(&[DefineArg {
+            name: "_FOO".to_string(),
+            expr: DefineExpr::number(0x1234),
+        }]);
+        assert_eq!(name, "*DEFINE*");
+        assert_eq!(summary.symbols.len(), 1);
+        assert_eq!(summary.symbols[0].name, b"_FOO".to_vec());
+        assert_eq!(summary.symbols[0].value, 0x1234);
+    }
+
+    #[test]
+    fn define_references_select_archive_members() {
+        let uniq = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .expect("time")
+            .as_nanos();
+        let dir = std::env::temp_dir().join(format!("rhlk-linker-define-{uniq}"));
+        fs::create_dir_all(&dir).expect("mkdir");
+        let main = dir.join("main.o");
+        let lib = dir.join("libx.a");
+        fs::write(&main, [0x00, 0x00]).expect("write main");
+        fs::write(&lib, make_simple_ar(&[("func.o", &obj_with_def("_libfunc"))])).expect("write lib");
+
+        let args = Args::parse_from([
+            "rhlk".to_string(),
+            "-d".to_string(),
+            "alias=_libfunc".to_string(),
+            main.to_string_lossy().to_string(),
+            lib.to_string_lossy().to_string(),
+        ]);
+        let runtime = args.runtime_config();
+        let inputs = args.inputs.clone();
+        let prepared = prepare_objects(args, runtime, inputs).expect("alias pulls in func.o");
+        let names = prepared
+            .input_names
+            .iter()
+            .map(|n| Path::new(n).file_name().and_then(|f| f.to_str()).unwrap_or(n))
+            .collect::<Vec<_>>();
+        assert_eq!(names, vec!["main.o", "libx.a(func.o)", "*DEFINE*"]);
+
+        let _ = fs::remove_file(main);
+        let _ = fs::remove_file(lib);
+        let _ = fs::remove_dir(dir);
+    }
+
+    #[test]
+    fn define_overrides_an_object_definition() {
+        let uniq = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .expect("time")
+            .as_nanos();
+        let dir = std::env::temp_dir().join(format!("rhlk-linker-define-override-{uniq}"));
+        fs::create_dir_all(&dir).expect("mkdir");
+        let obj = dir.join("a.o");
+        fs::write(&obj, obj_with_def("foo")).expect("write a.o");
+
+        for allow in [false, true] {
+            let mut cmdline = vec!["rhlk".to_string(), "-d".to_string(), "foo=0x1234".to_string()];
+            if allow {
+                cmdline.push("--allow-multiple-definition".to_string());
+            }
+            cmdline.push(obj.to_string_lossy().to_string());
+            let args = Args::parse_from(cmdline);
+            let runtime = args.runtime_config();
+            let inputs = args.inputs.clone();
+            let prepared = prepare_objects(args, runtime, inputs).expect("-d is not a second definition");
+            let defs = prepared
+                .summaries
+                .iter()
+                .flat_map(|s| &s.symbols)
+                .filter(|sym| sym.name == b"foo")
+                .map(|sym| (sym.section, sym.value))
+                .collect::<Vec<_>>();
+            assert_eq!(defs, vec![(SectionKind::Abs, 0x1234)]);
+        }
+
+        let _ = fs::remove_file(obj);
+        let _ = fs::remove_dir(dir);
     }
 
     #[test]
@@ -1708,6 +2811,38 @@ mod tests {
             .any(|s| s.name == b"___rsize" && s.section == SectionKind::Abs));
     }
 
+    #[test]
+    fn injects_stack_object_after_other_stacks() {
+        let obj = ObjectFile {
+            commands: vec![
+                Command::Header {
+                    section: 0x04,
+                    size: 6,
+                    name: b"stack".to_vec(),
+                },
+                Command::End,
+            ],
+            scd_tail: Vec::new(),
+        };
+        let mut summaries = vec![resolve_object(&obj)];
+        let mut objects = vec![obj];
+        let mut names = vec!["start.o".to_string()];
+        inject_stack_object(0x1000, &mut objects, &mut summaries, &mut names);
+        assert_eq!(names, vec!["start.o", "*STACK*"]);
+
+        let layout = plan_layout(&summaries);
+        assert_eq!(layout.total_size_by_section.get(&SectionKind::Stack), Some(&0x1006));
+        let addrs = symbol_addresses(&summaries, &layout).expect("addresses");
+        assert_eq!(
+            addrs.get(b"___stack_bottom".as_slice()),
+            Some(&(SectionKind::Stack, 6))
+        );
+        assert_eq!(
+            addrs.get(b"___stack_top".as_slice()),
+            Some(&(SectionKind::Stack, 0x1006))
+        );
+    }
+
     #[test]
     fn updates_section_info_rsize_symbol_from_layout_totals() {
         let mut objects = Vec::new();
@@ -1741,4 +2876,457 @@ mod tests {
             .expect("___rsize");
         assert_eq!(rsize, 4);
     }
+
+    #[test]
+    fn resolves_symbolic_define_expressions_after_layout() {
+        let args = Args::parse_from([
+            "rhlk",
+            "-d",
+            "alias=_main+2",
+            "--defsym",
+            "len=_end-_main",
+            "-d",
+            "twice=alias+len",
+            "in.o",
+        ]);
+        let obj = |name: &[u8]| ObjectFile {
+            commands: vec![
+                Command::Header {
+                    section: 0x01,
+                    size: 6,
+                    name: b"text".to_vec(),
+                },
+                Command::DefineSymbol {
+                    section: 0x01,
+                    value: 0,
+                    name: name.to_vec(),
+                },
+                Command::End,
+            ],
+            scd_tail: Vec::new(),
+        };
+        let mut objects = vec![obj(b"_start"), obj(b"_main"), obj(b"_end")];
+        let mut summaries = objects.iter().map(resolve_object).collect::<Vec<_>>();
+        let mut names = vec!["a.o".to_string(), "b.o".to_string(), "c.o".to_string()];
+        let (name, obj, summary) = define_symbols_object(&args.defines);
+        assert_eq!(summary.xrefs.len(), 4);
+        objects.push(obj);
+        summaries.push(summary);
+        names.push(name);
+        let layout = plan_layout(&summaries);
+        resolve_define_symbols(&args.defines, &mut summaries, &layout, &names).expect("resolve");
+
+        let find = |name: &[u8]| {
+            let sym = summaries[3].symbols.iter().find(|s| s.name == name).expect("symbol");
+            (sym.section, sym.value)
+        };
+        assert_eq!(find(b"alias"), (SectionKind::Text, 8));
+        assert_eq!(find(b"len"), (SectionKind::Abs, 6));
+        assert_eq!(find(b"twice"), (SectionKind::Text, 14));
+    }
+
+    #[test]
+    fn rejects_define_expression_with_two_relocatable_terms() {
+        let args = Args::parse_from(["rhlk", "-d", "bad=_main+_main", "in.o"]);
+        let obj = ObjectFile {
+            commands: vec![
+                Command::Header {
+                    section: 0x01,
+                    size: 2,
+                    name: b"text".to_vec(),
+                },
+                Command::DefineSymbol {
+                    section: 0x01,
+                    value: 0,
+                    name: b"_main".to_vec(),
+                },
+                Command::End,
+            ],
+            scd_tail: Vec::new(),
+        };
+        let (name, _, summary) = define_symbols_object(&args.defines);
+        let mut summaries = vec![resolve_object(&obj), summary];
+        let names = vec!["a.o".to_string(), name];
+        let layout = plan_layout(&summaries);
+        let err = resolve_define_symbols(&args.defines, &mut summaries, &layout, &names)
+            .expect_err("must fail");
+        assert!(err.to_string().contains("不正な式 in *DEFINE*: bad"));
+    }
+
+    #[test]
+    fn define_expression_names_the_unsupported_section_of_its_symbol() {
+        let args = Args::parse_from(["rhlk", "-d", "alias=_table+2", "-d", "gone=_missing", "in.o"]);
+        let obj = ObjectFile {
+            commands: vec![
+                Command::Header {
+                    section: 0x05,
+                    size: 4,
+                    name: b"rdata".to_vec(),
+                },
+                Command::DefineSymbol {
+                    section: 0x05,
+                    value: 0,
+                    name: b"_table".to_vec(),
+                },
+                Command::End,
+            ],
+            scd_tail: Vec::new(),
+        };
+        let (name, _, summary) = define_symbols_object(&args.defines);
+        let mut summaries = vec![resolve_object(&obj), summary];
+        let names = vec!["a.o".to_string(), name];
+        let layout = plan_layout(&summaries);
+        let err = resolve_define_symbols(&args.defines[..1], &mut summaries, &layout, &names)
+            .expect_err("rdata symbol");
+        assert_eq!(err.to_string(), "_table is in an unsupported section (rdata) in *DEFINE*");
+        let err = resolve_define_symbols(&args.defines[1..], &mut summaries, &layout, &names)
+            .expect_err("undefined symbol");
+        assert_eq!(err.to_string(), "未定義シンボル: _missing in *DEFINE*");
+    }
+
+    #[test]
+    fn injects_only_referenced_symbol_file_definitions() {
+        let uniq = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .expect("time")
+            .as_nanos();
+        let dir = std::env::temp_dir().join(format!("rhlk-linker-symfile-{uniq}"));
+        fs::create_dir_all(&dir).expect("mkdir");
+        let table = dir.join("iocs.sym");
+        fs::write(&table, b"foo equ $E88001\nbar=1 ; unused\nfoo = 0xe88001\n").expect("write");
+
+        let runtime = Args::parse_from(["rhlk", "-w", "in.o"]).runtime_config();
+        let defs = read_symbol_files(std::slice::from_ref(&table), runtime).expect("read");
+        assert_eq!(defs.len(), 2);
+        let main = parse_object(&obj_with_xref_and_request("foo", "libx.a")).expect("main parse");
+        let (name, obj, sum) = symbol_file_object(&defs);
+        let mut summaries = vec![resolve_object(&main), sum];
+        let mut objects = vec![main, obj];
+        let mut names = vec!["main.o".to_string(), name];
+        prune_symbol_file_definitions(&defs, runtime, &mut objects, &mut summaries, &mut names);
+        assert_eq!(names[1], "*SYMFILE*");
+        assert_eq!(summaries[1].symbols.len(), 1);
+        assert_eq!(summaries[1].symbols[0].name, b"foo".to_vec());
+        assert_eq!(summaries[1].symbols[0].section, SectionKind::Abs);
+        assert_eq!(summaries[1].symbols[0].value, 0x00e8_8001);
+
+        // With nothing referenced the object is dropped altogether.
+        summaries[0].xrefs.clear();
+        prune_symbol_file_definitions(&defs, runtime, &mut objects, &mut summaries, &mut names);
+        assert_eq!(names, vec!["main.o"]);
+
+        fs::write(&table, b"foo=1\nfoo=2\n").expect("write");
+        let err = read_symbol_files(std::slice::from_ref(&table), runtime).expect_err("must fail");
+        assert!(err.to_string().contains("シンボルが二重定義されています: foo"));
+
+        let _ = fs::remove_file(table);
+        let _ = fs::remove_dir(dir);
+    }
+
+    #[test]
+    fn symbol_file_definitions_keep_archive_members_out() {
+        let uniq = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .expect("time")
+            .as_nanos();
+        let dir = std::env::temp_dir().join(format!("rhlk-linker-symfile-ar-{uniq}"));
+        fs::create_dir_all(&dir).expect("mkdir");
+        let table = dir.join("rom.sym");
+        let main = dir.join("main.o");
+        let lib = dir.join("libx.a");
+        fs::write(&table, b"_romfunc=$ff0000\n").expect("write table");
+        fs::write(&main, obj_with_xref("_romfunc")).expect("write main");
+        fs::write(&lib, make_simple_ar(&[("rom.o", &obj_with_def("_romfunc"))])).expect("write lib");
+
+        let args = Args::parse_from([
+            "rhlk".to_string(),
+            "--symbol-file".to_string(),
+            table.to_string_lossy().to_string(),
+            main.to_string_lossy().to_string(),
+            lib.to_string_lossy().to_string(),
+        ]);
+        let runtime = args.runtime_config();
+        let inputs = args.inputs.clone();
+        let prepared = prepare_objects(args, runtime, inputs).expect("prepare");
+        let names = prepared
+            .input_names
+            .iter()
+            .map(|n| Path::new(n).file_name().and_then(|f| f.to_str()).unwrap_or(n))
+            .collect::<Vec<_>>();
+        assert_eq!(names, vec!["main.o", "*SYMFILE*"]);
+
+        let _ = fs::remove_file(table);
+        let _ = fs::remove_file(main);
+        let _ = fs::remove_file(lib);
+        let _ = fs::remove_dir(dir);
+    }
+
+    #[test]
+    fn scd_mode_fails_drops_or_strips_debug_info() {
+        let good = vec![0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0];
+        let make = || {
+            let mut objects = vec![
+                parse_object(&obj_with_def("_a")).expect("parse"),
+                parse_object(&obj_with_def("_b")).expect("parse"),
+            ];
+            objects[0].scd_tail.clone_from(&good);
+            objects[1].scd_tail = vec![0, 0, 0, 6];
+            let summaries = objects.iter().map(resolve_object).collect::<Vec<_>>();
+            (objects, summaries)
+        };
+        let names = vec!["a.o".to_string(), "b.o".to_string()];
+        let runtime = Args::parse_from(["rhlk", "-w", "in.o"]).runtime_config();
+
+        let (mut objects, summaries) = make();
+        let err = apply_scd_mode(ScdMode::Strict, runtime, &mut objects, &summaries, &names)
+            .expect_err("strict must fail");
+        assert_eq!(err.to_string(), "SCD header is truncated (4 bytes) in b.o");
+
+        let (mut objects, summaries) = make();
+        apply_scd_mode(ScdMode::Lenient, runtime, &mut objects, &summaries, &names).expect("lenient");
+        assert_eq!(objects[0].scd_tail, good);
+        assert!(objects[1].scd_tail.is_empty());
+
+        let (mut objects, summaries) = make();
+        apply_scd_mode(ScdMode::Strip, runtime, &mut objects, &summaries, &names).expect("strip");
+        assert!(objects.iter().all(|obj| obj.scd_tail.is_empty()));
+    }
+
+    #[test]
+    fn just_symbols_resolve_xrefs_as_absolute_before_archive_selection() {
+        let uniq = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .expect("time")
+            .as_nanos();
+        let dir = std::env::temp_dir().join(format!("rhlk-linker-just-{uniq}"));
+        fs::create_dir_all(&dir).expect("mkdir");
+
+        let mut resident = vec![0u8; 64];
+        resident[0] = b'H';
+        resident[1] = b'U';
+        resident[12..16].copy_from_slice(&2u32.to_be_bytes());
+        resident[28..32].copy_from_slice(&20u32.to_be_bytes());
+        resident.extend_from_slice(&[0x4e, 0x75]);
+        resident.extend_from_slice(&[0x02, 0x01, 0x00, 0x00, 0x00, 0x00, b'f', b'o', b'o', 0x00]);
+        resident.extend_from_slice(&[0x02, 0x01, 0x00, 0x00, 0x00, 0x00, b'b', b'a', b'r', 0x00]);
+        let resident_path = dir.join("resident.x");
+        fs::write(&resident_path, resident).expect("write resident");
+
+        let main = dir.join("main.o");
+        let lib = dir.join("libx.a");
+        fs::write(&main, obj_with_xref_and_request("foo", "libx.a")).expect("write main");
+        fs::write(&lib, make_simple_ar(&[("foo.o", &obj_with_def("foo"))])).expect("write lib");
+
+        let arg = JustSymbolsArg {
+            path: resident_path.clone(),
+            load_base: Some(0x0003_0000),
+        };
+        let mut ctx = LoadContext {
+            preloaded: vec![load_just_symbols(&arg).expect("just symbols")],
+            ..LoadContext::default()
+        };
+        let (mut objects, mut sums, names) =
+            load_objects_with_requests_paths(std::slice::from_ref(&main), &mut ctx).expect("must load");
+        prune_just_symbols(&mut objects, &mut sums, 1);
+        assert_eq!(names.len(), 2);
+        assert!(names[0].ends_with("resident.x"));
+        assert_eq!(sums[0].symbols.len(), 1);
+        assert_eq!(sums[0].symbols[0].name, b"foo".to_vec());
+        assert_eq!(sums[0].symbols[0].section, SectionKind::Abs);
+        assert_eq!(sums[0].symbols[0].value, 0x0003_0000);
+
+        let _ = fs::remove_file(resident_path);
+        let _ = fs::remove_file(main);
+        let _ = fs::remove_file(lib);
+        let _ = fs::remove_dir(dir);
+    }
+
+    #[test]
+    fn overlays_bind_to_root_and_follow_root_image() {
+        let uniq = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .expect("time")
+            .as_nanos();
+        let dir = std::env::temp_dir().join(format!("rhlk-linker-overlay-{uniq}"));
+        fs::create_dir_all(&dir).expect("mkdir");
+        let ov1 = dir.join("ov1.o");
+        let ov2 = dir.join("ov2.o");
+        fs::write(&ov1, obj_with_def_and_xref("_ov1", "_main")).expect("write ov1");
+        fs::write(&ov2, obj_with_def_and_xref("_ov2", "_ov1")).expect("write ov2");
+
+        let root = ObjectFile {
+            commands: vec![
+                Command::Header {
+                    section: 0x01,
+                    size: 6,
+                    name: b"text".to_vec(),
+                },
+                Command::DefineSymbol {
+                    section: 0x01,
+                    value: 2,
+                    name: b"_main".to_vec(),
+                },
+                Command::DefineSymbol {
+                    section: 0xff,
+                    value: 1,
+                    name: b"_ov1".to_vec(),
+                },
+                Command::End,
+            ],
+            scd_tail: Vec::new(),
+        };
+        let mut objects = vec![root];
+        let mut summaries = objects.iter().map(resolve_object).collect::<Vec<_>>();
+        let mut names = vec!["main.o".to_string()];
+        let overlay = |name: &str, input: &Path| OverlayArg {
+            output: dir.join(name),
+            inputs: vec![input.to_path_buf()],
+        };
+
+        let siblings = [overlay("a.r", &ov1), overlay("b.r", &ov2)];
+        let groups =
+            load_overlay_groups(&siblings, &summaries, &LoadContext::default()).expect("load");
+        let err = validate_overlay_references(&groups).expect_err("sibling reference");
+        assert!(err.to_string().contains("sibling overlay"));
+
+        let mut groups = load_overlay_groups(&siblings[..1], &summaries, &LoadContext::default())
+            .expect("load");
+        validate_overlay_references(&groups).expect("resolved via root");
+        inject_overlay_placeholders(&groups, &mut objects, &mut summaries, &mut names);
+        assert_eq!(names.last().map(String::as_str), Some("*OVERLAY*"));
+        validate_unresolved_symbols(&summaries, &names).expect("root resolved via overlay");
+
+        let layout = plan_layout(&summaries);
+        let (base, _) =
+            link_overlays(&mut groups, &mut summaries, &layout, &names, Some(0x0001_0000), true).expect("link");
+        assert_eq!(base, Some(0x0001_0006));
+        assert_eq!(summaries[1].symbols[0].name, b"_ov1".to_vec());
+        assert_eq!(summaries[1].symbols[0].value, 0x0001_0006);
+        let root_sym = &groups[0].summaries[0].symbols;
+        assert_eq!(root_sym.len(), 1);
+        assert_eq!((root_sym[0].name.as_slice(), root_sym[0].value), (&b"_main"[..], 0x0001_0002));
+
+        let _ = fs::remove_file(ov1);
+        let _ = fs::remove_file(ov2);
+        let _ = fs::remove_dir(dir);
+    }
+
+    #[test]
+    fn x_root_and_overlay_relocate_their_cross_references() {
+        let uniq = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .expect("time")
+            .as_nanos();
+        let dir = std::env::temp_dir().join(format!("rhlk-linker-overlay-x-{uniq}"));
+        fs::create_dir_all(&dir).expect("mkdir");
+        // text: jsr <xref 1>.l, defining `def` at `def_at`.
+        let jsr_object = |def: &str, def_at: u8, xref: &str| {
+            let mut out = vec![0xc0, 0x01, 0, 0, 0, 6];
+            out.extend_from_slice(b"text\0\0");
+            for (section, value, name) in [(0x01, def_at, def), (0xff, 1, xref)] {
+                out.extend_from_slice(&[0xb2, section, 0, 0, 0, value]);
+                out.extend_from_slice(name.as_bytes());
+                out.push(0);
+                if out.len() % 2 == 1 {
+                    out.push(0);
+                }
+            }
+            out.extend_from_slice(&[0x20, 0x01, 0, 0, 0, 0]);
+            out.extend_from_slice(&[0x10, 0x01, 0x4e, 0xb9, 0x42, 0xff, 0x00, 0x01, 0x00, 0x00]);
+            out
+        };
+        let main = dir.join("main.o");
+        let ov = dir.join("ov.o");
+        fs::write(&main, jsr_object("_main", 2, "_ov")).expect("write main");
+        fs::write(&ov, jsr_object("_ov", 0, "_main")).expect("write ov");
+        let main_x = dir.join("main.x");
+        let ov_x = dir.join("ov.x");
+        let overlay = format!("--overlay={}={}", ov_x.display(), ov.display());
+        run(Args::parse_from([
+            "rhlk".as_ref(),
+            "-o".as_ref(),
+            main_x.as_os_str(),
+            overlay.as_ref(),
+            main.as_os_str(),
+        ]))
+        .expect("link");
+
+        // The overlay is loaded right after the 6-byte root: the root reaches
+        // `_ov` at +6 and the overlay reaches `_main` at -4, both relocated.
+        for (path, target) in [(&main_x, 6u32), (&ov_x, 0xffff_fffc)] {
+            let image = fs::read(path).expect("read image");
+            assert_eq!(&image[0..2], b"HU");
+            assert_eq!(&image[64..66], &[0x4e, 0xb9]);
+            assert_eq!(image[66..70], target.to_be_bytes());
+            assert_eq!(image[24..28], 2u32.to_be_bytes());
+            assert_eq!(&image[70..72], &[0x00, 0x02]);
+        }
+
+        for path in [main, ov, main_x, ov_x] {
+            let _ = fs::remove_file(path);
+        }
+        let _ = fs::remove_dir(dir);
+    }
+
+    #[test]
+    fn converts_every_max_size_section_to_a_budget() {
+        let args = crate::cli::MAX_SIZE_SECTIONS
+            .iter()
+            .map(|name| MaxSizeArg {
+                section: (*name).to_string(),
+                limit: 4,
+            })
+            .collect::<Vec<_>>();
+        let budgets = size_budgets(&args);
+        assert_eq!(budgets.len(), args.len());
+        assert_eq!(budgets[0].section, Some(SectionKind::Text));
+        assert_eq!(budgets.last().map(|b| b.section), Some(None));
+        assert_eq!(target_cpu(CpuModel::M68040), Cpu::M68040);
+    }
+
+    #[test]
+    fn debug_file_follows_the_main_output_and_needs_symbols() {
+        let uniq = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .expect("time")
+            .as_nanos();
+        let dir = std::env::temp_dir().join(format!("rhlk-linker-debug-{uniq}"));
+        fs::create_dir_all(&dir).expect("mkdir");
+        let input = dir.join("foo.o");
+        fs::write(&input, [0x00, 0x00]).expect("write input");
+        let output = dir.join("foo.r");
+        let debug = dir.join("foo.dbg");
+        let debug_arg = format!("--debug-file={}", debug.display());
+        let link_args = |output: &Path| {
+            Args::parse_from([
+                "rhlk".as_ref(),
+                "-r".as_ref(),
+                "-o".as_ref(),
+                output.as_os_str(),
+                debug_arg.as_ref(),
+                input.as_os_str(),
+            ])
+        };
+
+        // A main output that cannot be written leaves no sidecar behind.
+        fs::create_dir_all(&output).expect("mkdir output");
+        run(link_args(&output)).expect_err("output is a directory");
+        assert!(!debug.exists());
+        fs::remove_dir(&output).expect("rmdir output");
+        run(link_args(&output)).expect("run");
+        assert!(output.exists() && debug.exists());
+
+        for cut in ["-x", "--strip-all"] {
+            let args = Args::parse_from(["rhlk", "-r", cut, "--debug-file=a.dbg", "a.o"]);
+            assert_eq!(
+                validate_args(&args).expect_err("no symbols").to_string(),
+                "--debug-file cannot be used with -x or --strip-all: the debug file would have no symbols"
+            );
+        }
+
+        let _ = fs::remove_file(output);
+        let _ = fs::remove_file(debug);
+        let _ = fs::remove_file(input);
+        let _ = fs::remove_dir(dir);
+    }
 }
@@ -0,0 +1,273 @@
+use std::collections::{HashMap, HashSet};
+use std::path::PathBuf;
+
+use crate::cli::OverlayArg;
+use crate::format::obj::{Command, ObjectFile};
+use crate::layout::{plan_layout, LayoutPlan};
+use crate::resolver::{resolve_object, ObjectSummary, SectionKind, Symbol};
+use crate::target::{layout_end, X68000};
+use crate::writer::{symbol_addresses, write_overlay};
+
+use super::{load_objects_with_requests_paths, validate_unresolved_symbols, LoadContext};
+
+const ROOT_OBJECT: &str = "*ROOT*";
+const OVERLAY_OBJECT: &str = "*OVERLAY*";
+
+/// One `--overlay` group. Object 0 is always the `*ROOT*` placeholder that
+/// carries the root definitions the group refers to.
+pub(super) struct OverlayGroup {
+    output: PathBuf,
+    objects: Vec<ObjectFile>,
+    pub(super) summaries: Vec<ObjectSummary>,
+    input_names: Vec<String>,
+}
+
+/// Loads every overlay group with the root definitions preloaded, so archive
+/// members already linked into the root are not pulled into overlays again.
+pub(super) fn load_overlay_groups(
+    overlays: &[OverlayArg],
+    root_summaries: &[ObjectSummary],
+    root_ctx: &LoadContext,
+) -> anyhow::Result<Vec<OverlayGroup>> {
+    let root_defs = root_summaries
+        .iter()
+        .flat_map(|s| s.symbols.iter())
+        .filter(|sym| sym.name.first() != Some(&b'*'))
+        .map(|sym| sym.name.clone())
+        .collect::<HashSet<_>>();
+    let mut groups = Vec::new();
+    for overlay in overlays {
+        let (root_obj, root_sum) = placeholder_object(root_defs.iter().cloned());
+        let mut ctx = LoadContext {
+            verbose: root_ctx.verbose,
+            trace_symbols: root_ctx.trace_symbols.clone(),
+            wraps: root_ctx.wraps.clone(),
+            preloaded: vec![(ROOT_OBJECT.to_string(), root_obj, root_sum)],
+            extractions: Vec::new(),
+        };
+        let (mut objects, mut summaries, input_names) =
+            load_objects_with_requests_paths(&overlay.inputs, &mut ctx)?;
+
+        // Keep only root symbols the overlay uses and does not define itself.
+        let own_defs = defined_names(&summaries[1..]);
+        let referenced = summaries
+            .iter()
+            .flat_map(|s| s.xrefs.iter().map(|x| x.name.clone()))
+            .collect::<HashSet<_>>();
+        let keep = |name: &Vec<u8>| referenced.contains(name) && !own_defs.contains(name);
+        summaries[0].symbols.retain(|sym| keep(&sym.name));
+        objects[0].commands.retain(|cmd| match cmd {
+            Command::DefineSymbol { name, .. } => keep(name),
+            _ => true,
+        });
+        groups.push(OverlayGroup {
+            output: overlay.output.clone(),
+            objects,
+            summaries,
+            input_names,
+        });
+    }
+    Ok(groups)
+}
+
+/// Rejects references between sibling overlays and anything left unresolved.
+pub(super) fn validate_overlay_references(groups: &[OverlayGroup]) -> anyhow::Result<()> {
+    let defs = groups
+        .iter()
+        .map(|g| defined_names(&g.summaries))
+        .collect::<Vec<_>>();
+    for (idx, group) in groups.iter().enumerate() {
+        for (obj_idx, summary) in group.summaries.iter().enumerate() {
+            for xr in &summary.xrefs {
+                if defs[idx].contains(&xr.name) {
+                    continue;
+                }
+                if let Some(sibling) =
+                    (0..groups.len()).find(|&i| i != idx && defs[i].contains(&xr.name))
+                {
+                    anyhow::bail!(
+                        "overlay {} cannot refer to {} in sibling overlay {} (in {})",
+                        group.output.display(),
+                        String::from_utf8_lossy(&xr.name),
+                        groups[sibling].output.display(),
+                        group.input_names[obj_idx]
+                    );
+                }
+            }
+        }
+        validate_unresolved_symbols(&group.summaries, &group.input_names)?;
+    }
+    Ok(())
+}
+
+/// Adds an `*OVERLAY*` object with placeholders for overlay symbols the root uses.
+pub(super) fn inject_overlay_placeholders(
+    groups: &[OverlayGroup],
+    objects: &mut Vec<ObjectFile>,
+    summaries: &mut Vec<ObjectSummary>,
+    input_names: &mut Vec<String>,
+) {
+    let root_defs = defined_names(summaries);
+    let overlay_defs = groups
+        .iter()
+        .flat_map(|g| defined_names(&g.summaries[1..]))
+        .collect::<HashSet<_>>();
+    let mut wanted = Vec::<Vec<u8>>::new();
+    for xr in summaries.iter().flat_map(|s| s.xrefs.iter()) {
+        if !root_defs.contains(&xr.name)
+            && overlay_defs.contains(&xr.name)
+            && !wanted.contains(&xr.name)
+        {
+            wanted.push(xr.name.clone());
+        }
+    }
+    if wanted.is_empty() {
+        return;
+    }
+    let (obj, sum) = placeholder_object(wanted.into_iter());
+    objects.push(obj);
+    summaries.push(sum);
+    input_names.push(OVERLAY_OBJECT.to_string());
+}
+
+/// Places overlays right after the root image and binds root and overlay symbols.
+///
+/// With `base_address` the root is loaded there and the cross references are
+/// absolute. Without it the root is a relocatable `.x` that loads the overlays
+/// right after its image (DOS `_EXEC` mode 3), so the cross references become
+/// text-relative and are relocated along with the image they appear in.
+///
+/// Returns the overlay base address, if fixed, and each overlay's layout.
+pub(super) fn link_overlays(
+    groups: &mut [OverlayGroup],
+    root_summaries: &mut [ObjectSummary],
+    root_layout: &LayoutPlan,
+    root_names: &[String],
+    base_address: Option<u32>,
+    target_check: bool,
+) -> anyhow::Result<(Option<u32>, Vec<LayoutPlan>)> {
+    let root_start = base_address.unwrap_or(0);
+    let overlay_start = root_start
+        .checked_add(root_layout.image_size())
+        .and_then(|v| v.checked_add(1))
+        .map(|v| v & !1)
+        .ok_or_else(|| anyhow::anyhow!("overlay base address overflows"))?;
+    // Binds a placeholder to `addr` in the image at `start`. A relocatable one
+    // becomes text-relative to `origin`, where its own (empty) text sits; an
+    // overlay refers back into the root through a wrapped negative offset.
+    let bind = |sym: &mut Symbol, start: u32, (section, addr): (SectionKind, u32), origin: u32| {
+        if section == SectionKind::Abs {
+            sym.value = addr;
+        } else if base_address.is_some() {
+            sym.value = start.wrapping_add(addr);
+        } else {
+            sym.section = SectionKind::Text;
+            sym.value = start.wrapping_add(addr).wrapping_sub(origin);
+        }
+    };
+
+    let root_addrs = symbol_addresses(root_summaries, root_layout)?;
+    let outputs = groups.iter().map(|g| g.output.clone()).collect::<Vec<_>>();
+    let mut layouts = Vec::new();
+    let mut overlay_addrs = HashMap::<Vec<u8>, ((SectionKind, u32), usize)>::new();
+    for (idx, group) in groups.iter_mut().enumerate() {
+        for sym in &mut group.summaries[0].symbols {
+            if let Some(&addr) = root_addrs.get(&sym.name) {
+                bind(sym, root_start, addr, overlay_start);
+            }
+        }
+        let layout = plan_layout(&group.summaries);
+        let what = format!("overlay {}", group.output.display());
+        layout_end(&what, &layout, overlay_start)?;
+        if target_check {
+            X68000.check_layout(&what, &layout, Some(overlay_start))?;
+        }
+        let own_defs = defined_names(&group.summaries[1..]);
+        for (name, addr) in symbol_addresses(&group.summaries, &layout)? {
+            if !own_defs.contains(&name) {
+                continue;
+            }
+            if let Some(&(prev, prev_idx)) = overlay_addrs.get(&name) {
+                if prev != addr {
+                    anyhow::bail!(
+                        "overlay symbol {} has different addresses in {} and {}",
+                        String::from_utf8_lossy(&name),
+                        outputs[prev_idx].display(),
+                        outputs[idx].display()
+                    );
+                }
+                continue;
+            }
+            overlay_addrs.insert(name, (addr, idx));
+        }
+        layouts.push(layout);
+    }
+
+    if let Some(pos) = root_names.iter().position(|n| n == OVERLAY_OBJECT) {
+        let origin = root_layout.placements[pos]
+            .by_section
+            .get(&SectionKind::Text)
+            .copied()
+            .unwrap_or(0);
+        for sym in &mut root_summaries[pos].symbols {
+            if let Some(&(addr, _)) = overlay_addrs.get(&sym.name) {
+                bind(sym, overlay_start, addr, origin);
+            }
+        }
+    }
+    Ok((base_address.map(|_| overlay_start), layouts))
+}
+
+/// Writes every overlay, as a fixed-address `.r` image at `overlay_base` or,
+/// without one, as a relocatable `.x` image.
+pub(super) fn write_overlays(
+    groups: &[OverlayGroup],
+    layouts: &[LayoutPlan],
+    overlay_base: Option<u32>,
+    g2lk_mode: bool,
+    verbose: bool,
+) -> anyhow::Result<()> {
+    for (group, layout) in groups.iter().zip(layouts) {
+        let output = group.output.to_string_lossy();
+        write_overlay(
+            output.as_ref(),
+            &group.objects,
+            &group.input_names,
+            &group.summaries,
+            layout,
+            overlay_base,
+            g2lk_mode,
+        )?;
+        if verbose {
+            match overlay_base {
+                Some(base) => println!("wrote overlay: {} at {base:#x}", group.output.display()),
+                None => println!("wrote overlay: {}", group.output.display()),
+            }
+        }
+    }
+    Ok(())
+}
+
+fn placeholder_object(names: impl Iterator<Item = Vec<u8>>) -> (ObjectFile, ObjectSummary) {
+    let mut commands = names
+        .map(|name| Command::DefineSymbol {
+            section: 0x00,
+            value: 0,
+            name,
+        })
+        .collect::<Vec<_>>();
+    commands.push(Command::End);
+    let obj = ObjectFile {
+        commands,
+        scd_tail: Vec::new(),
+    };
+    let sum = resolve_object(&obj);
+    (obj, sum)
+}
+
+fn defined_names(summaries: &[ObjectSummary]) -> HashSet<Vec<u8>> {
+    summaries
+        .iter()
+        .flat_map(|s| s.symbols.iter().map(|sym| sym.name.clone()))
+        .collect()
+}
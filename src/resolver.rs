@@ -44,6 +44,27 @@ impl SectionKind {
             _ => Self::Unknown(section),
         }
     }
+
+    #[must_use]
+    pub fn is_common(self) -> bool {
+        matches!(self, Self::Common | Self::RCommon | Self::RLCommon)
+    }
+
+    /// Sections addressed from the base register rather than the load address.
+    #[must_use]
+    pub fn is_relative(self) -> bool {
+        matches!(
+            self,
+            Self::RData
+                | Self::RBss
+                | Self::RStack
+                | Self::RLData
+                | Self::RLBss
+                | Self::RLStack
+                | Self::RCommon
+                | Self::RLCommon
+        )
+    }
 }
 
 #[derive(Debug, Clone, PartialEq, Eq)]
@@ -135,6 +156,42 @@ pub fn resolve_object(object: &ObjectFile) -> ObjectSummary {
     }
 }
 
+/// Rewrites xrefs for GNU-style `--wrap=SYM`.
+///
+/// References to `SYM` become `__wrap_SYM` and references to `__real_SYM`
+/// become `SYM`. C-level names carrying the leading `_` are handled the same
+/// way (`_SYM` -> `___wrap_SYM`, `___real_SYM` -> `_SYM`). Definitions are
+/// left untouched.
+pub fn apply_wraps(summary: &mut ObjectSummary, wraps: &[Vec<u8>]) {
+    if wraps.is_empty() {
+        return;
+    }
+    for xref in &mut summary.xrefs {
+        if let Some(name) = wrapped_name(&xref.name, wraps) {
+            xref.name = name;
+        }
+    }
+}
+
+fn wrapped_name(name: &[u8], wraps: &[Vec<u8>]) -> Option<Vec<u8>> {
+    for wrap in wraps {
+        let wrap = wrap.as_slice();
+        // Match the name as written first, then its C-level form with the leading `_`.
+        for prefix in [&b""[..], b"_"] {
+            let Some(bare) = name.strip_prefix(prefix) else {
+                continue;
+            };
+            if bare == wrap {
+                return Some([prefix, b"__wrap_", wrap].concat());
+            }
+            if bare.strip_prefix(b"__real_".as_slice()) == Some(wrap) {
+                return Some([prefix, wrap].concat());
+            }
+        }
+    }
+    None
+}
+
 fn usize_to_u32_saturating(value: usize) -> u32 {
     u32::try_from(value).unwrap_or(u32::MAX)
 }
@@ -147,7 +204,7 @@ fn bump_usage(map: &mut BTreeMap<SectionKind, u32>, section: SectionKind, amount
 #[cfg(test)]
 mod tests {
     use crate::format::obj::{Command, ObjectFile};
-    use crate::resolver::{resolve_object, SectionKind};
+    use crate::resolver::{apply_wraps, resolve_object, SectionKind};
 
     #[test]
     fn collects_sizes_symbols_and_requests() {
@@ -214,6 +271,44 @@ mod tests {
         assert_eq!(summary.start_address, Some((0x01, 0x100)));
     }
 
+    #[test]
+    fn wraps_xrefs_and_maps_real_back_to_original() {
+        let object = ObjectFile {
+            commands: vec![
+                Command::DefineSymbol {
+                    section: 0xff,
+                    value: 1,
+                    name: b"_malloc".to_vec(),
+                },
+                Command::DefineSymbol {
+                    section: 0xff,
+                    value: 2,
+                    name: b"___real_malloc".to_vec(),
+                },
+                Command::DefineSymbol {
+                    section: 0xff,
+                    value: 3,
+                    name: b"free".to_vec(),
+                },
+                Command::DefineSymbol {
+                    section: 0x01,
+                    value: 0,
+                    name: b"_free".to_vec(),
+                },
+                Command::End,
+            ],
+            scd_tail: Vec::new(),
+        };
+        let mut summary = resolve_object(&object);
+        apply_wraps(&mut summary, &[b"malloc".to_vec(), b"free".to_vec()]);
+        let names = summary.xrefs.iter().map(|x| x.name.clone()).collect::<Vec<_>>();
+        assert_eq!(
+            names,
+            vec![b"___wrap_malloc".to_vec(), b"_malloc".to_vec(), b"__wrap_free".to_vec()]
+        );
+        assert_eq!(summary.symbols[0].name, b"_free".to_vec());
+    }
+
     #[test]
     fn extracts_object_align_from_special_symbol() {
         let object = ObjectFile {
@@ -0,0 +1,350 @@
+use thiserror::Error;
+
+use crate::layout::LayoutPlan;
+use crate::resolver::SectionKind;
+use crate::writer::section_tag;
+
+/// A fixed hardware area a loaded program must not overlap. `end` is exclusive.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Region {
+    pub name: &'static str,
+    pub start: u32,
+    pub end: u32,
+}
+
+/// Address space of the machine the output is loaded on.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TargetModel {
+    pub address_bits: u32,
+    /// End of main RAM; loaded sections must stay below it.
+    pub ram_limit: u32,
+    pub regions: &'static [Region],
+}
+
+const X68000_REGIONS: [Region; 5] = [
+    Region {
+        name: "exception vectors",
+        start: 0x0000_0000,
+        end: 0x0000_0400,
+    },
+    Region {
+        name: "graphics VRAM",
+        start: 0x00c0_0000,
+        end: 0x00e0_0000,
+    },
+    Region {
+        name: "text VRAM",
+        start: 0x00e0_0000,
+        end: 0x00e8_0000,
+    },
+    Region {
+        name: "I/O area",
+        start: 0x00e8_0000,
+        end: 0x00f0_0000,
+    },
+    Region {
+        name: "ROM",
+        start: 0x00f0_0000,
+        end: 0x0100_0000,
+    },
+];
+
+/// X68000: 68000 with a 24-bit bus and up to 12MB of main RAM.
+pub const X68000: TargetModel = TargetModel {
+    address_bits: 24,
+    ram_limit: 0x00c0_0000,
+    regions: &X68000_REGIONS,
+};
+
+/// CPU the output runs on; decides which accesses must be aligned.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Cpu {
+    #[default]
+    M68000,
+    M68020,
+    M68030,
+    M68040,
+    M68060,
+}
+
+impl Cpu {
+    #[must_use]
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::M68000 => "68000",
+            Self::M68020 => "68020",
+            Self::M68030 => "68030",
+            Self::M68040 => "68040",
+            Self::M68060 => "68060",
+        }
+    }
+
+    /// The 68000 raises an address error on word/long accesses at odd addresses.
+    #[must_use]
+    pub fn requires_even_access(self) -> bool {
+        self == Self::M68000
+    }
+
+    /// 32-bit `(d32,An)`/`(d32,PC)` displacements arrived with the 68020.
+    #[must_use]
+    pub fn has_long_displacement(self) -> bool {
+        self >= Self::M68020
+    }
+
+    /// 68020 and later read a long in one bus cycle only at 4-byte boundaries.
+    #[must_use]
+    pub fn prefers_long_alignment(self) -> bool {
+        self >= Self::M68020
+    }
+}
+
+/// Loaded sections in memory order, as the X loader places them.
+const LOADED_SECTIONS: [SectionKind; 7] = [
+    SectionKind::Text,
+    SectionKind::Data,
+    SectionKind::RData,
+    SectionKind::RLData,
+    SectionKind::Bss,
+    SectionKind::Common,
+    SectionKind::Stack,
+];
+
+#[derive(Debug, Clone, PartialEq, Eq, Error)]
+pub enum TargetError {
+    #[error("{what} at {start:#x} + {size:#x} overflows the 32-bit address space")]
+    Overflow { what: String, start: u32, size: u32 },
+    #[error("{what} {start:#x}-{end:#x} is outside the {bits}-bit address space")]
+    AddressSpace {
+        what: String,
+        start: u32,
+        end: u64,
+        bits: u32,
+    },
+    #[error("{what} {start:#x}-{end:#x} overlaps {region} ({region_start:#x}-{region_end:#x})")]
+    Hardware {
+        what: String,
+        start: u32,
+        end: u64,
+        region: &'static str,
+        region_start: u32,
+        region_end: u32,
+    },
+    #[error("{what} {start:#x}-{end:#x} exceeds main RAM (ends at {limit:#x})")]
+    Ram {
+        what: String,
+        start: u32,
+        end: u64,
+        limit: u32,
+    },
+}
+
+/// `start + size`, failing instead of clamping.
+///
+/// # Errors
+/// Returns `TargetError::Overflow` when the sum does not fit in 32 bits.
+pub fn checked_end(what: &str, start: u32, size: u32) -> Result<u32, TargetError> {
+    start
+        .checked_add(size)
+        .ok_or_else(|| TargetError::Overflow {
+            what: what.to_string(),
+            start,
+            size,
+        })
+}
+
+/// End of the loaded sections of `layout` placed at `start`; this is the
+/// overflow check every link runs, independent of any target model.
+///
+/// # Errors
+/// Returns `TargetError::Overflow` when a section ends past 32 bits.
+pub fn layout_end(what: &str, layout: &LayoutPlan, start: u32) -> Result<u32, TargetError> {
+    let mut cursor = start;
+    for section in LOADED_SECTIONS {
+        let size = layout
+            .total_size_by_section
+            .get(&section)
+            .copied()
+            .unwrap_or(0);
+        if size != 0 {
+            cursor = checked_end(
+                &format!("{what} {} section", section_tag(section)),
+                cursor,
+                size,
+            )?;
+        }
+    }
+    Ok(cursor)
+}
+
+impl TargetModel {
+    /// Checks that `size` bytes at `start` fit the address space, avoid every
+    /// hardware region and stay within main RAM.
+    ///
+    /// # Errors
+    /// Returns the first `TargetError` the range violates.
+    pub fn check_range(&self, what: &str, start: u32, size: u32) -> Result<(), TargetError> {
+        let end = u64::from(checked_end(what, start, size)?);
+        let space = 1u64 << self.address_bits;
+        if u64::from(start) >= space || end > space {
+            return Err(TargetError::AddressSpace {
+                what: what.to_string(),
+                start,
+                end,
+                bits: self.address_bits,
+            });
+        }
+        // An empty range only claims its start address.
+        let last = end.max(u64::from(start) + 1);
+        if let Some(region) = self
+            .regions
+            .iter()
+            .find(|r| u64::from(start) < u64::from(r.end) && last > u64::from(r.start))
+        {
+            return Err(TargetError::Hardware {
+                what: what.to_string(),
+                start,
+                end,
+                region: region.name,
+                region_start: region.start,
+                region_end: region.end,
+            });
+        }
+        if end > u64::from(self.ram_limit) {
+            return Err(TargetError::Ram {
+                what: what.to_string(),
+                start,
+                end,
+                limit: self.ram_limit,
+            });
+        }
+        Ok(())
+    }
+
+    /// Checks every loaded section of `layout` placed at `base`; a relocatable
+    /// image (`base` of `None`) only has to fit in main RAM.
+    ///
+    /// # Errors
+    /// Returns the first `TargetError` a section (or the whole image) violates.
+    pub fn check_layout(
+        &self,
+        what: &str,
+        layout: &LayoutPlan,
+        base: Option<u32>,
+    ) -> Result<(), TargetError> {
+        let start = base.unwrap_or(0);
+        let mut cursor = start;
+        for section in LOADED_SECTIONS {
+            let size = layout
+                .total_size_by_section
+                .get(&section)
+                .copied()
+                .unwrap_or(0);
+            if size == 0 {
+                continue;
+            }
+            let name = format!("{what} {} section", section_tag(section));
+            if base.is_some() {
+                self.check_range(&name, cursor, size)?;
+            }
+            cursor = checked_end(&name, cursor, size)?;
+        }
+        match base {
+            Some(base) => self.check_range(what, base, cursor - base),
+            None if cursor > self.ram_limit => Err(TargetError::Ram {
+                what: what.to_string(),
+                start,
+                end: u64::from(cursor),
+                limit: self.ram_limit,
+            }),
+            None => Ok(()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+
+    use super::{layout_end, Cpu, TargetError, X68000};
+    use crate::layout::plan_layout;
+    use crate::resolver::{ObjectSummary, SectionKind};
+
+    fn summary(text: u32, bss: u32) -> ObjectSummary {
+        let mut declared = BTreeMap::new();
+        declared.insert(SectionKind::Text, text);
+        declared.insert(SectionKind::Bss, bss);
+        ObjectSummary {
+            object_align: 2,
+            declared_section_sizes: declared,
+            observed_section_usage: BTreeMap::new(),
+            symbols: Vec::new(),
+            xrefs: Vec::new(),
+            requests: Vec::new(),
+            start_address: None,
+        }
+    }
+
+    #[test]
+    fn rejects_ranges_outside_x68000_ram() {
+        X68000.check_range("base address", 0x6800, 0).expect("ram");
+        assert!(matches!(
+            X68000.check_range("base address", 0x100, 0),
+            Err(TargetError::Hardware {
+                region: "exception vectors",
+                ..
+            })
+        ));
+        assert!(matches!(
+            X68000.check_range("base address", 0x00e8_0000, 0),
+            Err(TargetError::Hardware {
+                region: "I/O area",
+                ..
+            })
+        ));
+        assert!(matches!(
+            X68000.check_range("base address", 0x0100_0000, 0),
+            Err(TargetError::AddressSpace { bits: 24, .. })
+        ));
+        assert!(matches!(
+            X68000.check_range("image", 0xffff_fff0, 0x20),
+            Err(TargetError::Overflow { .. })
+        ));
+
+        let layout = plan_layout(&[summary(0x100, 0x10_0000)]);
+        X68000
+            .check_layout("image", &layout, Some(0x1_0000))
+            .expect("fits");
+        let err = X68000
+            .check_layout("image", &layout, Some(0x00bf_0000))
+            .expect_err("bss in VRAM");
+        assert_eq!(
+            err.to_string(),
+            "image bss section 0xbf0100-0xcf0100 overlaps graphics VRAM (0xc00000-0xe00000)"
+        );
+        assert!(matches!(
+            layout_end("image", &layout, 0xffff_ff00),
+            Err(TargetError::Overflow { .. })
+        ));
+        assert_eq!(layout_end("image", &layout, 0), Ok(0x10_0100));
+        let huge = plan_layout(&[summary(0x100, 0x00c0_0000)]);
+        X68000
+            .check_layout("image", &layout, None)
+            .expect("relocatable");
+        assert!(matches!(
+            X68000.check_layout("image", &huge, None),
+            Err(TargetError::Ram {
+                limit: 0x00c0_0000,
+                ..
+            })
+        ));
+    }
+
+    #[test]
+    fn cpu_models_gate_alignment_rules() {
+        assert!(Cpu::M68000.requires_even_access());
+        assert!(!Cpu::M68000.has_long_displacement());
+        assert!(!Cpu::M68030.requires_even_access());
+        assert!(Cpu::M68030.has_long_displacement());
+        assert!(Cpu::M68040.prefers_long_alignment());
+    }
+}
@@ -1,23 +1,41 @@
-use std::collections::{BTreeMap, HashMap, HashSet};
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
 use std::path::Path;
 
 use anyhow::{bail, Context, Result};
 use thiserror::Error;
 
 use crate::format::obj::{Command, ObjectFile};
-use crate::layout::LayoutPlan;
+use crate::layout::{LayoutPlan, RELATIVE_SECTION_ORDER};
 use crate::resolver::{ObjectSummary, SectionKind, Symbol};
 
 mod map;
 pub use map::write_map;
 #[cfg(test)]
 pub(crate) use map::{MapSizes, build_map_text};
+mod budget;
+pub use budget::{check_size_budgets, render_memory_usage, SizeBudget};
+mod cpu;
+pub use cpu::check_cpu_rules;
 mod ctor_dtor;
+mod device;
 mod opcode;
+mod displacement;
 mod expr;
+mod islands;
+pub use islands::insert_branch_islands;
+mod prg;
+mod scd;
+pub use scd::{validate_scd, ScdProblem};
+mod selfreloc;
+mod sidecar;
+mod size_report;
+mod zfile;
+pub use size_report::{diff_size_reports, write_size_report, SizeReportFormat};
 
 const CTOR_LIST_SYM: &[u8] = b"___CTOR_LIST__";
 const DTOR_LIST_SYM: &[u8] = b"___DTOR_LIST__";
+/// First base-register offset a `d16(An)` reference cannot reach.
+const SHORT_RELATIVE_LIMIT: u32 = 0x8000;
 
 #[derive(Debug, Error)]
 enum WriterError {
@@ -30,12 +48,20 @@ pub enum OutputFormat {
     X,
     R,
     Mcs,
+    /// Human68k device driver: an X image whose text begins with a device header.
+    Sys,
+    /// Fixed-address `0x601A` image; needs a base address.
+    Z,
+    /// Atari TOS `.PRG` with DRI symbols and TOS fixups.
+    Prg,
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum RelocationCheck {
     Strict,
     Skip,
+    /// `.r` only: prepend a stub that applies the relocations on entry.
+    SelfRelocate,
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
@@ -50,16 +76,62 @@ pub enum SymbolTablePolicy {
     Cut,
 }
 
+/// `--keep-symbol`/`--strip-symbol` exceptions to the symbol table policy.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SymbolFilter {
+    /// Written even when the table is cut; a name in both lists is kept.
+    pub keep: BTreeSet<Vec<u8>>,
+    /// Left out even when the table is kept.
+    pub strip: BTreeSet<Vec<u8>>,
+}
+
+static NO_SYMBOL_FILTER: SymbolFilter = SymbolFilter {
+    keep: BTreeSet::new(),
+    strip: BTreeSet::new(),
+};
+
+/// The symbols an output table is built from.
 #[derive(Debug, Clone, Copy)]
+struct SymbolSelection<'a> {
+    policy: SymbolTablePolicy,
+    filter: &'a SymbolFilter,
+}
+
+impl<'a> SymbolSelection<'a> {
+    fn new(policy: SymbolTablePolicy, filter: &'a SymbolFilter) -> Self {
+        Self { policy, filter }
+    }
+
+    fn all() -> SymbolSelection<'static> {
+        SymbolSelection::new(SymbolTablePolicy::Keep, &NO_SYMBOL_FILTER)
+    }
+
+    /// A cut table is still written when some symbols are explicitly kept.
+    fn writes_table(self) -> bool {
+        self.policy == SymbolTablePolicy::Keep || !self.filter.keep.is_empty()
+    }
+
+    fn keeps(self, name: &[u8]) -> bool {
+        if self.filter.keep.contains(name) {
+            return true;
+        }
+        self.policy == SymbolTablePolicy::Keep && !self.filter.strip.contains(name)
+    }
+}
+
+#[derive(Debug, Clone)]
 pub struct OutputOptions {
     pub format: OutputFormat,
     pub relocation_check: RelocationCheck,
     pub bss_policy: BssPolicy,
     pub symbol_table: SymbolTablePolicy,
+    pub symbol_filter: SymbolFilter,
     pub base_address: u32,
     pub load_mode: u8,
     pub section_info: bool,
     pub g2lk_mode: bool,
+    /// `.r`/`.mcs` only: where to write the symbol and SCD companion file.
+    pub debug_file: Option<String>,
 }
 
 /// Writes a linked output image to `output_path`.
@@ -68,13 +140,29 @@ pub struct OutputOptions {
 /// Returns an error when validation, image generation, header patching, or file write fails.
 pub fn write_output(
     output_path: &str,
-    options: OutputOptions,
+    options: &OutputOptions,
     objects: &[ObjectFile],
     input_paths: &[String],
     summaries: &[ObjectSummary],
     layout: &LayoutPlan,
 ) -> Result<()> {
     validate_link_inputs(objects, input_paths, summaries, options.g2lk_mode)?;
+    islands::check_word_displacements(objects, input_paths, summaries, layout)?;
+    displacement::check_byte_displacements(objects, input_paths, summaries, layout)?;
+    if options.debug_file.is_some() && !matches!(options.format, OutputFormat::R | OutputFormat::Mcs) {
+        bail!("--debug-file needs an .r or .mcs output");
+    }
+
+    if matches!(options.format, OutputFormat::Z) {
+        let image = zfile::build_z_image(output_path, options, objects, input_paths, summaries, layout)?;
+        std::fs::write(output_path, image).with_context(|| format!("failed to write {output_path}"))?;
+        return Ok(());
+    }
+    if matches!(options.format, OutputFormat::Prg) {
+        let image = prg::build_prg_image(output_path, options, objects, summaries, layout)?;
+        std::fs::write(output_path, image).with_context(|| format!("failed to write {output_path}"))?;
+        return Ok(());
+    }
 
     if matches!(options.format, OutputFormat::R | OutputFormat::Mcs)
         && matches!(options.relocation_check, RelocationCheck::Strict)
@@ -82,7 +170,18 @@ pub fn write_output(
         validate_r_convertibility(objects, summaries, layout, output_path, options.g2lk_mode)?;
     }
 
-    let mut payload = if matches!(options.format, OutputFormat::R | OutputFormat::Mcs) {
+    // A self-relocating .r and a debug sidecar take relocation offsets and
+    // symbols from the same link as the payload.
+    let self_relocate = matches!(options.format, OutputFormat::R)
+        && matches!(options.relocation_check, RelocationCheck::SelfRelocate);
+    let linked = if self_relocate || options.debug_file.is_some() {
+        Some(link_image(objects, summaries, layout, options.g2lk_mode)?)
+    } else {
+        None
+    };
+    let mut payload = if let Some(linked) = &linked {
+        r_payload_from_linked(linked, layout, matches!(options.bss_policy, BssPolicy::Omit))
+    } else if matches!(options.format, OutputFormat::R | OutputFormat::Mcs) {
         build_r_payload(
             objects,
             summaries,
@@ -91,13 +190,12 @@ pub fn write_output(
             options.g2lk_mode,
         )?
     } else {
-        build_x_image_with_options(
-            objects,
-            summaries,
-            layout,
-            matches!(options.symbol_table, SymbolTablePolicy::Keep),
-            options.g2lk_mode,
-        )
+        let symbols = SymbolSelection::new(options.symbol_table, &options.symbol_filter);
+        if matches!(options.format, OutputFormat::Sys) {
+            build_device_image(objects, summaries, layout, symbols, options.g2lk_mode)
+        } else {
+            build_x_image_with_options(objects, summaries, layout, symbols, options.g2lk_mode)
+        }
         .map_err(|err| {
             if err
                 .downcast_ref::<WriterError>()
@@ -107,13 +205,15 @@ pub fn write_output(
                     "再配置対象が奇数アドレスにあります: {}",
                     to_human68k_path(Path::new(output_path))
                 )
+            } else if let Some(e) = err.downcast_ref::<device::DeviceHeaderError>() {
+                anyhow::anyhow!("{e}: {}", to_human68k_path(Path::new(output_path)))
             } else {
                 err
             }
         })?
     };
 
-    if matches!(options.format, OutputFormat::X) && (options.base_address != 0 || options.load_mode != 0) {
+    if matches!(options.format, OutputFormat::X | OutputFormat::Sys) && (options.base_address != 0 || options.load_mode != 0) {
         apply_x_header_options(&mut payload, options.base_address, options.load_mode)?;
     }
     if options.section_info {
@@ -125,6 +225,13 @@ pub fn write_output(
         )?;
     }
 
+    let mut load_offset = 0;
+    if let Some(linked) = linked.as_ref().filter(|_| self_relocate) {
+        let program_len = payload.len();
+        payload = selfreloc::prepend_relocation_stub(&payload, &linked.relocations, linked.exec);
+        load_offset = usize_to_u32_saturating(payload.len() - program_len);
+    }
+
     if matches!(options.format, OutputFormat::Mcs) {
         let bss_extra = if matches!(options.bss_policy, BssPolicy::Omit) {
             0
@@ -139,9 +246,65 @@ pub fn write_output(
         })?;
     }
     std::fs::write(output_path, payload).with_context(|| format!("failed to write {output_path}"))?;
+    if let (Some(debug_path), Some(linked)) = (&options.debug_file, &linked) {
+        let sidecar = sidecar::build_debug_sidecar(options, linked, objects, summaries, layout, load_offset)?;
+        std::fs::write(debug_path, sidecar).with_context(|| format!("failed to write {debug_path}"))?;
+    }
+    Ok(())
+}
+
+/// Writes an overlay as a raw `.r` image linked to run at `base_address`, or
+/// without one as an `.x` image the root relocates when it loads it.
+///
+/// A fixed overlay has every relocatable long resolved against the base, so
+/// it carries no relocation table and must be loaded exactly there. An `.x`
+/// overlay has no symbol table, since it would list the root's symbols too.
+///
+/// # Errors
+/// Returns an error when validation, image generation, or file write fails.
+pub fn write_overlay(
+    output_path: &str,
+    objects: &[ObjectFile],
+    input_paths: &[String],
+    summaries: &[ObjectSummary],
+    layout: &LayoutPlan,
+    base_address: Option<u32>,
+    g2lk_mode: bool,
+) -> Result<()> {
+    validate_link_inputs(objects, input_paths, summaries, g2lk_mode)?;
+    islands::check_word_displacements(objects, input_paths, summaries, layout)?;
+    displacement::check_byte_displacements(objects, input_paths, summaries, layout)?;
+    let image = if let Some(base) = base_address {
+        build_fixed_image(objects, summaries, layout, base, g2lk_mode)?.0
+    } else {
+        let symbols = SymbolSelection::new(SymbolTablePolicy::Cut, &NO_SYMBOL_FILTER);
+        build_x_image_with_options(objects, summaries, layout, symbols, g2lk_mode)?
+    };
+    std::fs::write(output_path, image).with_context(|| format!("failed to write {output_path}"))?;
     Ok(())
 }
 
+/// Returns the section and image-relative address of every defined symbol.
+///
+/// # Errors
+/// Returns an error when an address overflows 32 bits.
+pub fn symbol_addresses(
+    summaries: &[ObjectSummary],
+    layout: &LayoutPlan,
+) -> Result<HashMap<Vec<u8>, (SectionKind, u32)>> {
+    Ok(build_global_symbol_addrs(
+        summaries,
+        layout,
+        section_total(layout, SectionKind::Text),
+        data_image_size(layout),
+        section_total(layout, SectionKind::Bss),
+        section_total(layout, SectionKind::Common),
+    )?
+    .into_iter()
+    .map(|(name, sym)| (name, (sym.section, sym.addr)))
+    .collect())
+}
+
 fn patch_section_size_info(
     payload: &mut [u8],
     r_format: bool,
@@ -152,7 +315,7 @@ fn patch_section_size_info(
     let data_size = section_total(layout, SectionKind::Data);
     let bss_only = section_total(layout, SectionKind::Bss);
     let common_only = section_total(layout, SectionKind::Common);
-    let addrs = build_global_symbol_addrs(summaries, layout, text_size, data_size, bss_only, common_only);
+    let addrs = build_global_symbol_addrs(summaries, layout, text_size, data_size, bss_only, common_only)?;
     let Some(sym) = addrs.get(b"___size_info".as_slice()) else {
         bail!("section info symbol is missing: ___size_info");
     };
@@ -219,13 +382,21 @@ fn section_total(layout: &LayoutPlan, section: SectionKind) -> u32 {
     layout.total_size_by_section.get(&section).copied().unwrap_or(0)
 }
 
+/// Data as loaded: `data` followed by the `rdata` and `rldata` images that
+/// startup code copies into the base-relative area.
+fn data_image_size(layout: &LayoutPlan) -> u32 {
+    section_total(layout, SectionKind::Data)
+        .saturating_add(section_total(layout, SectionKind::RData))
+        .saturating_add(section_total(layout, SectionKind::RLData))
+}
+
 fn bss_common_stack_total(layout: &LayoutPlan) -> u32 {
     section_total(layout, SectionKind::Bss)
         .saturating_add(section_total(layout, SectionKind::Common))
         .saturating_add(section_total(layout, SectionKind::Stack))
 }
 
-fn section_tag(section: SectionKind) -> &'static str {
+pub(crate) fn section_tag(section: SectionKind) -> &'static str {
     match section {
         SectionKind::Abs => "abs",
         SectionKind::Text => "text",
@@ -252,25 +423,107 @@ fn build_x_image(
     summaries: &[ObjectSummary],
     layout: &LayoutPlan,
 ) -> Result<Vec<u8>> {
-    build_x_image_with_options(objects, summaries, layout, true, false)
+    build_x_image_with_options(objects, summaries, layout, SymbolSelection::all(), false)
 }
 
 fn build_x_image_with_options(
     objects: &[ObjectFile],
     summaries: &[ObjectSummary],
     layout: &LayoutPlan,
-    include_symbols: bool,
+    symbols: SymbolSelection<'_>,
     g2lk_mode: bool,
 ) -> Result<Vec<u8>> {
+    let linked = link_image(objects, summaries, layout, g2lk_mode)?;
+    assemble_x_image(&linked, objects, summaries, layout, symbols)
+}
+
+/// Builds a device driver image, rejecting it unless text starts with a valid device header.
+fn build_device_image(
+    objects: &[ObjectFile],
+    summaries: &[ObjectSummary],
+    layout: &LayoutPlan,
+    symbols: SymbolSelection<'_>,
+    g2lk_mode: bool,
+) -> Result<Vec<u8>> {
+    let linked = link_image(objects, summaries, layout, g2lk_mode)?;
+    device::validate_device_header(&linked.text, &linked.relocations, linked.exec)?;
+    assemble_x_image(&linked, objects, summaries, layout, symbols)
+}
+
+fn assemble_x_image(
+    linked: &LinkedImage,
+    objects: &[ObjectFile],
+    summaries: &[ObjectSummary],
+    layout: &LayoutPlan,
+    symbols: SymbolSelection<'_>,
+) -> Result<Vec<u8>> {
+    let symbol_data = if symbols.writes_table() {
+        build_symbol_table(symbols, summaries, layout, linked)?
+    } else {
+        Vec::new()
+    };
+    let symbol_size = usize_to_u32_saturating(symbol_data.len());
+    let reloc_table = encode_relocation_offsets(&linked.relocations);
+    let reloc_size = usize_to_u32_saturating(reloc_table.len());
+    let (scd_line, scd_info, scd_name) = build_scd_passthrough(objects, summaries, layout)?;
+    let scd_line_size = usize_to_u32_saturating(scd_line.len());
+    let scd_info_size = usize_to_u32_saturating(scd_info.len());
+    let scd_name_size = usize_to_u32_saturating(scd_name.len());
+
+    let header = build_x_header(XHeader {
+        text_size: linked.text_size,
+        data_size: linked.data_size,
+        bss_size: linked.bss_size,
+        reloc_size,
+        symbol_size,
+        scd_line_size,
+        scd_info_size,
+        scd_name_size,
+        exec: linked.exec,
+    });
+
+    let mut image = header;
+    image.extend_from_slice(&linked.text);
+    image.extend_from_slice(&linked.data);
+    image.extend_from_slice(&reloc_table);
+    image.extend_from_slice(&symbol_data);
+    image.extend_from_slice(&scd_line);
+    image.extend_from_slice(&scd_info);
+    image.extend_from_slice(&scd_name);
+    Ok(image)
+}
+
+/// Text/data after symbol patching, plus what the container formats need around it.
+struct LinkedImage {
+    text: Vec<u8>,
+    data: Vec<u8>,
+    text_size: u32,
+    data_size: u32,
+    bss_only: u32,
+    common_only: u32,
+    bss_size: u32,
+    exec: u32,
+    relocations: Vec<u32>,
+    g2lk_synth: Option<G2lkSyntheticSymbols>,
+}
+
+fn link_image(
+    objects: &[ObjectFile],
+    summaries: &[ObjectSummary],
+    layout: &LayoutPlan,
+    g2lk_mode: bool,
+) -> Result<LinkedImage> {
     if objects.len() != summaries.len() || objects.len() != layout.placements.len() {
         bail!("internal mismatch: objects/summaries/layout length differs");
     }
 
+    check_short_relative_range(summaries, layout)?;
+
     let mut linked = link_initialized_sections(
         objects,
         summaries,
         layout,
-        &[SectionKind::Text, SectionKind::Data],
+        &[SectionKind::Text, SectionKind::Data, SectionKind::RData, SectionKind::RLData],
     )?;
 
     let text_size = linked
@@ -280,6 +533,9 @@ fn build_x_image_with_options(
         .get(&SectionKind::Data)
         .map_or(0, |v| usize_to_u32_saturating(v.len()));
     let (data_size, g2lk_synth) = extend_data_for_g2lk(&mut linked, objects, g2lk_mode, text_size, data_size);
+    let data_size = data_size
+        .saturating_add(section_total(layout, SectionKind::RData))
+        .saturating_add(section_total(layout, SectionKind::RLData));
     let bss_only = section_total(layout, SectionKind::Bss);
     let common_only = section_total(layout, SectionKind::Common);
     let stack_only = section_total(layout, SectionKind::Stack);
@@ -295,62 +551,58 @@ fn build_x_image_with_options(
         bss_only,
         common_only,
         g2lk_synth,
-    );
+    )?;
     patch_opaque_commands(
         &mut linked,
         objects,
         summaries,
         layout,
         &global_symbol_addrs,
-    );
+    )?;
     ctor_dtor::patch_ctor_dtor_tables(&mut linked, objects, layout, &global_symbol_addrs, text_size)?;
 
-    let text = linked.get(&SectionKind::Text).cloned().unwrap_or_default();
-    let data = linked.get(&SectionKind::Data).cloned().unwrap_or_default();
-
-    let symbol_data = if include_symbols {
-        build_symbol_table(
-            summaries,
-            layout,
-            text_size,
-            data_size,
-            bss_only,
-            common_only,
-            g2lk_synth,
-        )
-    } else {
-        Vec::new()
-    };
-    let symbol_size = usize_to_u32_saturating(symbol_data.len());
-    let reloc_table = build_relocation_table(objects, summaries, layout, text_size, &global_symbol_addrs)?;
-    let reloc_size = usize_to_u32_saturating(reloc_table.len());
-    let (scd_line, scd_info, scd_name) = build_scd_passthrough(objects, summaries, layout)?;
-    let scd_line_size = usize_to_u32_saturating(scd_line.len());
-    let scd_info_size = usize_to_u32_saturating(scd_info.len());
-    let scd_name_size = usize_to_u32_saturating(scd_name.len());
-
+    let relocations = collect_relocation_offsets(objects, summaries, layout, text_size, &global_symbol_addrs)?;
     let exec = resolve_exec_address(summaries, text_size, data_size, bss_size)?.unwrap_or(0);
-    let header = build_x_header(XHeader {
+    let mut data = linked.remove(&SectionKind::Data).unwrap_or_default();
+    for section in [SectionKind::RData, SectionKind::RLData] {
+        data.extend_from_slice(&linked.remove(&section).unwrap_or_default());
+    }
+    Ok(LinkedImage {
+        text: linked.remove(&SectionKind::Text).unwrap_or_default(),
+        data,
         text_size,
         data_size,
+        bss_only,
+        common_only,
         bss_size,
-        reloc_size,
-        symbol_size,
-        scd_line_size,
-        scd_info_size,
-        scd_name_size,
         exec,
-    });
+        relocations,
+        g2lk_synth,
+    })
+}
 
-    let mut image = header;
-    image.extend_from_slice(&text);
-    image.extend_from_slice(&data);
-    image.extend_from_slice(&reloc_table);
-    image.extend_from_slice(&symbol_data);
-    image.extend_from_slice(&scd_line);
-    image.extend_from_slice(&scd_info);
-    image.extend_from_slice(&scd_name);
-    Ok(image)
+/// Builds text+data with every relocatable long resolved for a fixed load address.
+///
+/// Returns the image and its entry offset.
+fn build_fixed_image(
+    objects: &[ObjectFile],
+    summaries: &[ObjectSummary],
+    layout: &LayoutPlan,
+    base_address: u32,
+    g2lk_mode: bool,
+) -> Result<(Vec<u8>, u32)> {
+    let linked = link_image(objects, summaries, layout, g2lk_mode)?;
+    let mut image = linked.text;
+    image.extend_from_slice(&linked.data);
+    for off in linked.relocations {
+        let at = u32_to_usize_saturating(off);
+        let Some(bytes) = image.get_mut(at..at.saturating_add(4)) else {
+            bail!("relocation offset is outside the image: {off:#x}");
+        };
+        let value = u32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]);
+        bytes.copy_from_slice(&value.wrapping_add(base_address).to_be_bytes());
+    }
+    Ok((image, linked.exec))
 }
 
 #[allow(clippy::similar_names)]
@@ -830,45 +1082,52 @@ fn i64_low_u32(value: i64) -> u32 {
 }
 
 fn build_symbol_table(
+    symbols: SymbolSelection<'_>,
     summaries: &[ObjectSummary],
     layout: &LayoutPlan,
-    text_size: u32,
-    data_size: u32,
-    bss_only: u32,
-    common_only: u32,
-    g2lk_synth: Option<G2lkSyntheticSymbols>,
-) -> Vec<u8> {
+    linked: &LinkedImage,
+) -> Result<Vec<u8>> {
     let mut out = Vec::new();
-    if let Some(synth) = g2lk_synth {
-        append_symbol_entry(&mut out, 0x0202, synth.ctor_addr, CTOR_LIST_SYM);
-        append_symbol_entry(&mut out, 0x0202, synth.dtor_addr, DTOR_LIST_SYM);
+    for (ty, addr, name) in collect_symbol_entries(symbols, summaries, layout, linked)? {
+        append_symbol_entry(&mut out, ty, addr, name);
+    }
+    Ok(out)
+}
+
+/// Symbols in output-table order as X-format `(type, value, name)` entries.
+fn collect_symbol_entries<'a>(
+    symbols: SymbolSelection<'_>,
+    summaries: &'a [ObjectSummary],
+    layout: &LayoutPlan,
+    linked: &LinkedImage,
+) -> Result<Vec<(u16, u32, &'a [u8])>> {
+    let mut out = Vec::new();
+    if let Some(synth) = linked.g2lk_synth {
+        out.push((0x0202, synth.ctor_addr, CTOR_LIST_SYM));
+        out.push((0x0202, synth.dtor_addr, DTOR_LIST_SYM));
     }
+    out.retain(|(_, _, name)| symbols.keeps(name));
     for (idx, summary) in summaries.iter().enumerate() {
+        let placement = base_relative_placement(layout, idx)?;
         for sym in &summary.symbols {
-            if sym.name.first() == Some(&b'*') {
+            if sym.name.first() == Some(&b'*') || !symbols.keeps(&sym.name) {
                 continue;
             }
             let Some((ty, addr)) = encode_symbol(
                 sym,
-                &layout.placements[idx].by_section,
-                text_size,
-                data_size,
-                bss_only,
-                common_only,
-            ) else {
+                &placement,
+                linked.text_size,
+                linked.data_size,
+                linked.bss_only,
+                linked.common_only,
+            )?
+            else {
                 continue;
             };
-
-            out.extend_from_slice(&ty.to_be_bytes());
-            out.extend_from_slice(&addr.to_be_bytes());
-            out.extend_from_slice(&sym.name);
-            out.push(0);
-            if out.len() % 2 != 0 {
-                out.push(0);
-            }
+            out.push((ty, addr, sym.name.as_slice()));
         }
     }
-    out
+    Ok(out)
 }
 
 fn append_symbol_entry(out: &mut Vec<u8>, ty: u16, addr: u32, name: &[u8]) {
@@ -888,48 +1147,26 @@ fn encode_symbol(
     data_size: u32,
     bss_only: u32,
     common_only: u32,
-) -> Option<(u16, u32)> {
-    match sym.section {
-        SectionKind::Text => Some((
-            0x0201,
-            placement
-                .get(&SectionKind::Text)
-                .copied()
-                .unwrap_or(0)
-                .saturating_add(sym.value),
-        )),
-        SectionKind::Data => Some((
-            0x0202,
-            text_size
-                .saturating_add(placement.get(&SectionKind::Data).copied().unwrap_or(0))
-                .saturating_add(sym.value),
-        )),
-        SectionKind::Bss => Some((
-            0x0203,
-            text_size
-                .saturating_add(data_size)
-                .saturating_add(placement.get(&SectionKind::Bss).copied().unwrap_or(0))
-                .saturating_add(sym.value),
-        )),
-        SectionKind::Stack => Some((
-            0x0204,
-            text_size
-                .saturating_add(data_size)
-                .saturating_add(bss_only)
-                .saturating_add(common_only)
-                .saturating_add(placement.get(&SectionKind::Stack).copied().unwrap_or(0))
-                .saturating_add(sym.value),
-        )),
-        SectionKind::Common => Some((0x0003, sym.value)),
-        SectionKind::Abs
-        | SectionKind::RData
+) -> Result<Option<(u16, u32)>> {
+    let ty = match sym.section {
+        SectionKind::Text => 0x0201,
+        SectionKind::Data => 0x0202,
+        SectionKind::Bss => 0x0203,
+        SectionKind::Stack => 0x0204,
+        SectionKind::Common => return Ok(Some((0x0003, sym.value))),
+        SectionKind::RCommon => return Ok(Some((0x0006, sym.value))),
+        SectionKind::RLCommon => return Ok(Some((0x0009, sym.value))),
+        SectionKind::Abs => return Ok(Some((0x0200, sym.value))),
+        SectionKind::RData
         | SectionKind::RBss
         | SectionKind::RStack
         | SectionKind::RLData
         | SectionKind::RLBss
-        | SectionKind::RLStack => Some((0x0200, sym.value)),
-        SectionKind::RCommon | SectionKind::RLCommon | SectionKind::Xref | SectionKind::Unknown(_) => None,
-    }
+        | SectionKind::RLStack => 0x0200 | relative_section_code(sym.section),
+        SectionKind::Xref | SectionKind::Unknown(_) => return Ok(None),
+    };
+    let addr = symbol_address(sym, placement, text_size, data_size, bss_only, common_only)?;
+    Ok(Some((ty, addr)))
 }
 
 fn build_r_payload(
@@ -959,18 +1196,20 @@ fn build_r_payload(
         summaries,
         layout,
         text_size,
-        data_size,
+        data_size
+            .saturating_add(section_total(layout, SectionKind::RData))
+            .saturating_add(section_total(layout, SectionKind::RLData)),
         bss_only,
         common_only,
         g2lk_synth,
-    );
+    )?;
     patch_opaque_commands(
         &mut linked,
         objects,
         summaries,
         layout,
         &global_symbol_addrs,
-    );
+    )?;
     ctor_dtor::patch_ctor_dtor_tables(&mut linked, objects, layout, &global_symbol_addrs, text_size)?;
 
     let mut payload = Vec::new();
@@ -993,6 +1232,18 @@ fn build_r_payload(
     Ok(payload)
 }
 
+/// The `.r` payload of an already linked image: text, data and (unless
+/// omitted) zeroed bss/common/stack.
+fn r_payload_from_linked(linked: &LinkedImage, layout: &LayoutPlan, omit_bss: bool) -> Vec<u8> {
+    let mut payload = linked.text.clone();
+    payload.extend_from_slice(&linked.data);
+    if !omit_bss {
+        let total = usize::try_from(bss_common_stack_total(layout)).unwrap_or(usize::MAX);
+        payload.resize(payload.len() + total, 0);
+    }
+    payload
+}
+
 fn link_initialized_sections(
     objects: &[ObjectFile],
     summaries: &[ObjectSummary],
@@ -1128,6 +1379,17 @@ fn build_relocation_table(
     total_text_size: u32,
     global_symbol_addrs: &HashMap<Vec<u8>, GlobalSymbolAddr>,
 ) -> Result<Vec<u8>> {
+    let offsets = collect_relocation_offsets(objects, summaries, layout, total_text_size, global_symbol_addrs)?;
+    Ok(encode_relocation_offsets(&offsets))
+}
+
+fn collect_relocation_offsets(
+    objects: &[ObjectFile],
+    summaries: &[ObjectSummary],
+    layout: &LayoutPlan,
+    total_text_size: u32,
+    global_symbol_addrs: &HashMap<Vec<u8>, GlobalSymbolAddr>,
+) -> Result<Vec<u32>> {
     let mut offsets = Vec::<u32>::new();
     for (idx, (obj, summary)) in objects.iter().zip(summaries.iter()).enumerate() {
         collect_object_relocations(
@@ -1137,14 +1399,14 @@ fn build_relocation_table(
             total_text_size,
             global_symbol_addrs,
             &mut offsets,
-        );
+        )?;
     }
     if let Some(odd) = offsets.iter().copied().find(|off| off & 1 != 0) {
         bail!(WriterError::RelocationTargetAddressIsOdd { offset: odd });
     }
     offsets.sort_unstable();
     offsets.dedup();
-    Ok(encode_relocation_offsets(&offsets))
+    Ok(offsets)
 }
 
 fn validate_r_convertibility(
@@ -1163,11 +1425,11 @@ fn validate_r_convertibility(
         summaries,
         layout,
         text_size,
-        data_size,
+        data_image_size(layout),
         bss_only,
         common_only,
         g2lk_synth,
-    );
+    )?;
     let reloc = build_relocation_table(objects, summaries, layout, text_size, &global_symbol_addrs)?;
     if !reloc.is_empty() {
         bail!(
@@ -1271,11 +1533,11 @@ fn build_global_symbol_addrs_with_g2lk(
     bss_only: u32,
     common_only: u32,
     g2lk_synth: Option<G2lkSyntheticSymbols>,
-) -> HashMap<Vec<u8>, GlobalSymbolAddr> {
+) -> Result<HashMap<Vec<u8>, GlobalSymbolAddr>> {
     let mut global_symbol_addrs =
-        build_global_symbol_addrs(summaries, layout, text_size, data_size, bss_only, common_only);
+        build_global_symbol_addrs(summaries, layout, text_size, data_size, bss_only, common_only)?;
     inject_g2lk_symbols(&mut global_symbol_addrs, g2lk_synth);
-    global_symbol_addrs
+    Ok(global_symbol_addrs)
 }
 
 fn extend_data_for_g2lk(
@@ -1309,18 +1571,38 @@ fn collect_object_relocations(
     total_text_size: u32,
     global_symbol_addrs: &HashMap<Vec<u8>, GlobalSymbolAddr>,
     out: &mut Vec<u32>,
-) {
-    walk_commands(object, |cmd, current, local, _calc_stack| {
+) -> Result<()> {
+    let mut overflow = None;
+    walk_commands(object, |cmd, current, local, calc_stack| {
         let Command::Opaque { code, payload } = cmd else {
             return;
         };
-        if opaque_write_size(*code) == 0 {
-            return;
-        }
-        if !matches!(current, SectionKind::Text | SectionKind::Data) {
-            return;
-        }
-        if !should_relocate(*code, payload, summary, global_symbol_addrs) {
+        let [hi, lo] = code.to_be_bytes();
+        let relocate = match hi {
+            opcode::OPH_PUSH_VALUE_BASE => {
+                if let Some(entry) = evaluate_push_80_for_patch(lo, payload, summary, global_symbol_addrs, placement) {
+                    calc_stack.push(entry);
+                }
+                return;
+            }
+            opcode::OPH_EXPR_BASE => {
+                let _ = expr::evaluate_a0(lo, calc_stack);
+                return;
+            }
+            // A long computed on the calc stack is relocated when it is still an address.
+            opcode::OPH_WRT_STK_LONG | opcode::OPH_WRT_STK_LONG_ALT | opcode::OPH_WRT_STK_LONG_RELOC => {
+                calc_stack.pop().is_some_and(|e| e.stat == 1)
+            }
+            opcode::OPH_WRT_STK_BYTE
+            | opcode::OPH_WRT_STK_WORD_TEXT
+            | opcode::OPH_WRT_STK_BYTE_RAW
+            | opcode::OPH_WRT_STK_WORD_RELOC => {
+                calc_stack.pop();
+                return;
+            }
+            _ => opaque_write_size(*code) != 0 && should_relocate(*code, payload, summary, global_symbol_addrs),
+        };
+        if !relocate || !matches!(current, SectionKind::Text | SectionKind::Data) {
             return;
         }
         let section_base = match current {
@@ -1328,8 +1610,14 @@ fn collect_object_relocations(
             _ => 0,
         };
         let placed = placement.get(&current).copied().unwrap_or(0);
-        out.push(section_base.saturating_add(placed).saturating_add(local));
+        match checked_address(&[section_base, placed, local], || "relocation offset".to_string()) {
+            Ok(offset) => out.push(offset),
+            Err(err) => {
+                overflow.get_or_insert(err);
+            }
+        }
     });
+    overflow.map_or(Ok(()), Err)
 }
 
 fn opaque_write_size(code: u16) -> u8 {
@@ -1429,36 +1717,19 @@ fn build_global_symbol_addrs(
     data_size: u32,
     bss_only: u32,
     common_only: u32,
-) -> HashMap<Vec<u8>, GlobalSymbolAddr> {
+) -> Result<HashMap<Vec<u8>, GlobalSymbolAddr>> {
     let mut map = HashMap::new();
     for (idx, summary) in summaries.iter().enumerate() {
-        let placement = &layout.placements[idx].by_section;
+        let placement = base_relative_placement(layout, idx)?;
         for sym in &summary.symbols {
-            let addr = match sym.section {
-                SectionKind::Text => placement
-                    .get(&SectionKind::Text)
-                    .copied()
-                    .unwrap_or(0)
-                    .saturating_add(sym.value),
-                SectionKind::Data => text_size
-                    .saturating_add(placement.get(&SectionKind::Data).copied().unwrap_or(0))
-                    .saturating_add(sym.value),
-                SectionKind::Bss => text_size
-                    .saturating_add(data_size)
-                    .saturating_add(placement.get(&SectionKind::Bss).copied().unwrap_or(0))
-                    .saturating_add(sym.value),
-                SectionKind::Stack => text_size
-                    .saturating_add(data_size)
-                    .saturating_add(bss_only)
-                    .saturating_add(common_only)
-                    .saturating_add(placement.get(&SectionKind::Stack).copied().unwrap_or(0))
-                    .saturating_add(sym.value),
-                SectionKind::Common => text_size
-                    .saturating_add(data_size)
-                    .saturating_add(bss_only)
-                    .saturating_add(sym.value),
-                _ => sym.value,
-            };
+            // The first real definition in link order wins; common entries only fill gaps.
+            if map
+                .get(&sym.name)
+                .is_some_and(|prev: &GlobalSymbolAddr| !prev.section.is_common())
+            {
+                continue;
+            }
+            let addr = symbol_address(sym, &placement, text_size, data_size, bss_only, common_only)?;
             map.insert(
                 sym.name.clone(),
                 GlobalSymbolAddr {
@@ -1468,7 +1739,90 @@ fn build_global_symbol_addrs(
             );
         }
     }
-    map
+    Ok(map)
+}
+
+fn symbol_address(
+    sym: &Symbol,
+    placement: &BTreeMap<SectionKind, u32>,
+    text_size: u32,
+    data_size: u32,
+    bss_only: u32,
+    common_only: u32,
+) -> Result<u32> {
+    let placed = |section| placement.get(&section).copied().unwrap_or(0);
+    let terms = match sym.section {
+        SectionKind::Text => vec![placed(SectionKind::Text), sym.value],
+        SectionKind::Data => vec![text_size, placed(SectionKind::Data), sym.value],
+        SectionKind::Bss => vec![text_size, data_size, placed(SectionKind::Bss), sym.value],
+        SectionKind::Stack => vec![
+            text_size,
+            data_size,
+            bss_only,
+            common_only,
+            placed(SectionKind::Stack),
+            sym.value,
+        ],
+        SectionKind::Common => vec![text_size, data_size, bss_only, sym.value],
+        section if section.is_relative() => vec![placed(section), sym.value],
+        _ => return Ok(sym.value),
+    };
+    checked_address(&terms, || format!("symbol {}", String::from_utf8_lossy(&sym.name)))
+}
+
+/// Sums the parts of an emitted address; past 32 bits it is an error rather
+/// than an address clamped to $ffffffff.
+fn checked_address(terms: &[u32], what: impl FnOnce() -> String) -> Result<u32> {
+    terms
+        .iter()
+        .try_fold(0u32, |sum, &term| sum.checked_add(term))
+        .ok_or_else(|| anyhow::anyhow!("{} overflows the 32-bit address space", what()))
+}
+
+/// An object's placement with base-relative sections moved to their offset
+/// from the base register; `rcommon`/`rlcommon` map to their block start.
+fn base_relative_placement(layout: &LayoutPlan, idx: usize) -> Result<BTreeMap<SectionKind, u32>> {
+    let mut placement = layout.placements[idx].by_section.clone();
+    for section in RELATIVE_SECTION_ORDER {
+        let start = layout.relative_section_start(section).unwrap_or(0);
+        let local = placement.get(&section).copied().unwrap_or(0);
+        placement.insert(section, checked_address(&[start, local], || section_name(section).to_string())?);
+    }
+    Ok(placement)
+}
+
+fn relative_section_code(section: SectionKind) -> u16 {
+    match section {
+        SectionKind::RData => 0x05,
+        SectionKind::RBss => 0x06,
+        SectionKind::RStack => 0x07,
+        SectionKind::RLData => 0x08,
+        SectionKind::RLBss => 0x09,
+        SectionKind::RLStack => 0x0a,
+        _ => 0x00,
+    }
+}
+
+/// `rdata`/`rbss` are reached through `d16(An)`, so every symbol in them must
+/// sit below $8000 from the base register.
+fn check_short_relative_range(summaries: &[ObjectSummary], layout: &LayoutPlan) -> Result<()> {
+    for (idx, summary) in summaries.iter().enumerate() {
+        let placement = base_relative_placement(layout, idx)?;
+        for sym in &summary.symbols {
+            if !matches!(sym.section, SectionKind::RData | SectionKind::RBss) {
+                continue;
+            }
+            let offset = symbol_address(sym, &placement, 0, 0, 0, 0)?;
+            if offset >= SHORT_RELATIVE_LIMIT {
+                bail!(
+                    "{} symbol {} is out of 16-bit base-relative range: offset ${offset:x}",
+                    section_tag(sym.section),
+                    String::from_utf8_lossy(&sym.name)
+                );
+            }
+        }
+    }
+    Ok(())
 }
 
 fn patch_opaque_commands(
@@ -1477,8 +1831,9 @@ fn patch_opaque_commands(
     summaries: &[ObjectSummary],
     layout: &LayoutPlan,
     global_symbol_addrs: &HashMap<Vec<u8>, GlobalSymbolAddr>,
-) {
+) -> Result<()> {
     for (idx, (obj, summary)) in objects.iter().zip(summaries.iter()).enumerate() {
+        let value_placement = base_relative_placement(layout, idx)?;
         walk_commands(obj, |cmd, current, local, calc_stack| {
             let Command::Opaque { code, payload } = cmd else {
                 return;
@@ -1486,7 +1841,7 @@ fn patch_opaque_commands(
             let [hi, lo] = code.to_be_bytes();
             if hi == opcode::OPH_PUSH_VALUE_BASE {
                 if let Some(entry) =
-                    evaluate_push_80_for_patch(lo, payload, summary, global_symbol_addrs)
+                    evaluate_push_80_for_patch(lo, payload, summary, global_symbol_addrs, &value_placement)
                 {
                     calc_stack.push(entry);
                 }
@@ -1500,7 +1855,7 @@ fn patch_opaque_commands(
                         payload,
                         summary,
                         global_symbol_addrs,
-                        &layout.placements[idx].by_section,
+                        &value_placement,
                     )
                 })
                 .and_then(|v| if is_initialized_section(current) { Some(v) } else { None })
@@ -1519,6 +1874,7 @@ fn patch_opaque_commands(
             }
         });
     }
+    Ok(())
 }
 
 fn materialize_stack_write_opaque(code: u16, calc_stack: &mut Vec<ExprEntry>) -> Option<Vec<u8>> {
@@ -1552,6 +1908,7 @@ fn evaluate_push_80_for_patch(
     payload: &[u8],
     summary: &ObjectSummary,
     global_symbol_addrs: &HashMap<Vec<u8>, GlobalSymbolAddr>,
+    placement: &BTreeMap<SectionKind, u32>,
 ) -> Option<ExprEntry> {
     if is_common_or_xref_section(lo) {
         let label_no = read_u16_be(payload)?;
@@ -1567,13 +1924,13 @@ fn evaluate_push_80_for_patch(
             value: u32_bits_to_i32(sym.addr),
         });
     }
-    if reloc_section_kind(lo).is_some() || is_abs_section(lo) {
+    if is_abs_section(lo) {
         let value = read_i32_be(payload)?;
-        let stat = match lo {
-            s if is_abs_section(s) => 0,
-            0x01..=0x04 => 1,
-            _ => 2,
-        };
+        return Some(ExprEntry { stat: 0, value });
+    }
+    if reloc_section_kind(lo).is_some() {
+        let value = section_value_with_placement(lo, read_i32_be(payload)?, placement)?;
+        let stat = if matches!(lo, 0x01..=0x04) { 1 } else { 2 };
         return Some(ExprEntry { stat, value });
     }
     None
@@ -1684,8 +2041,8 @@ fn should_relocate(
         return false;
     }
     let lo = code_lo(code);
-    if is_reloc_section(lo) {
-        return true;
+    if let Some(section) = reloc_section_kind(lo) {
+        return !section.is_relative();
     }
     if is_xref_section(lo) {
         let Some(label_no) = read_u16_be(payload) else {
@@ -1705,10 +2062,6 @@ fn should_relocate(
     false
 }
 
-fn is_reloc_section(sect: u8) -> bool {
-    reloc_section_kind(sect).is_some()
-}
-
 pub(super) fn reloc_section_kind(section: u8) -> Option<SectionKind> {
     let kind = SectionKind::from_u8(section);
     match kind {
@@ -1855,12 +2208,12 @@ fn resolve_exec_address(
         return Ok(None);
     };
     let (sect, addr) = start;
-    let base = match sect {
-        0x02 => text_size,
-        0x03 => text_size.saturating_add(data_size),
-        _ => 0,
+    let base: &[u32] = match sect {
+        0x02 => &[text_size],
+        0x03 => &[text_size, data_size],
+        _ => &[],
     };
-    Ok(Some(base.saturating_add(addr)))
+    Ok(Some(checked_address(&[base, &[addr]].concat(), || "start address".to_string())?))
 }
 
 fn validate_link_inputs(
@@ -2017,6 +2370,12 @@ fn collect_global_symbols(summaries: &[ObjectSummary]) -> HashMap<Vec<u8>, Symbo
     let mut global_symbols = HashMap::<Vec<u8>, Symbol>::new();
     for summary in summaries {
         for sym in &summary.symbols {
+            if global_symbols
+                .get(&sym.name)
+                .is_some_and(|prev| !prev.section.is_common())
+            {
+                continue;
+            }
             global_symbols.insert(sym.name.clone(), sym.clone());
         }
     }
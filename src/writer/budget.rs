@@ -0,0 +1,150 @@
+use std::fmt::Write as _;
+
+use anyhow::{bail, Result};
+
+use crate::layout::LayoutPlan;
+use crate::resolver::{ObjectSummary, SectionKind};
+
+use super::size_report::{build_size_report, REPORT_SECTIONS};
+
+/// Contributors named when a budget is exceeded.
+const CONTRIBUTORS_SHOWN: usize = 3;
+
+/// A `--max-size` limit; `section` of `None` limits the sum of all sections.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SizeBudget {
+    pub section: Option<SectionKind>,
+    pub limit: u32,
+}
+
+impl SizeBudget {
+    /// Looks up a budget by section name (`text`, `rdata`, ...) or `total`.
+    #[must_use]
+    pub fn named(name: &str, limit: u32) -> Option<Self> {
+        let section = if name == "total" {
+            None
+        } else {
+            Some(
+                *REPORT_SECTIONS
+                    .iter()
+                    .find(|s| super::section_tag(**s) == name)?,
+            )
+        };
+        Some(Self { section, limit })
+    }
+}
+
+/// Fails on the first budget the layout exceeds, naming the objects that
+/// contribute most to it.
+///
+/// # Errors
+/// Returns an error when a section (or the total) is larger than its limit.
+pub fn check_size_budgets(
+    budgets: &[SizeBudget],
+    summaries: &[ObjectSummary],
+    layout: &LayoutPlan,
+    input_paths: &[String],
+) -> Result<()> {
+    for budget in budgets {
+        let used = budget_used(budget.section, layout);
+        if used <= budget.limit {
+            continue;
+        }
+        let report = build_size_report(summaries, layout, input_paths)?;
+        let mut contributors = report
+            .objects
+            .iter()
+            .filter_map(|obj| {
+                let size = match budget.section {
+                    None => obj.total,
+                    Some(section) => obj
+                        .by_section
+                        .iter()
+                        .find(|(kind, _)| *kind == section)
+                        .map_or(0, |(_, size)| *size),
+                };
+                (size != 0).then_some((size, obj.name.as_str()))
+            })
+            .collect::<Vec<_>>();
+        contributors.sort_by(|a, b| b.0.cmp(&a.0).then_with(|| a.1.cmp(b.1)));
+        let mut message = format!(
+            "{} size {used:#x} exceeds --max-size limit {:#x} by {:#x}",
+            budget_name(budget.section),
+            budget.limit,
+            used - budget.limit
+        );
+        if !contributors.is_empty() {
+            let listed = contributors
+                .iter()
+                .take(CONTRIBUTORS_SHOWN)
+                .map(|(size, name)| format!("{name} ({size:#x})"))
+                .collect::<Vec<_>>()
+                .join(", ");
+            let _ = write!(message, "; biggest contributors: {listed}");
+        }
+        bail!(message);
+    }
+    Ok(())
+}
+
+/// `--print-memory-usage` table: every non-empty or budgeted section, then the total.
+#[must_use]
+pub fn render_memory_usage(budgets: &[SizeBudget], layout: &LayoutPlan) -> String {
+    let limit_of = |section: Option<SectionKind>| {
+        budgets
+            .iter()
+            .find(|b| b.section == section)
+            .map(|b| b.limit)
+    };
+    let mut out = String::new();
+    let _ = writeln!(
+        out,
+        "{:<10}{:>12}{:>12}{:>9}",
+        "section", "used", "limit", "%used"
+    );
+    let rows = REPORT_SECTIONS
+        .iter()
+        .copied()
+        .map(Some)
+        .chain(std::iter::once(None));
+    for section in rows {
+        let used = budget_used(section, layout);
+        let limit = limit_of(section);
+        if section.is_some() && used == 0 && limit.is_none() {
+            continue;
+        }
+        let (limit_text, percent) = match limit {
+            Some(limit) => (format!("{limit:#010x}"), percent_text(used, limit)),
+            None => ("-".to_string(), "-".to_string()),
+        };
+        let _ = writeln!(
+            out,
+            "{:<10}{:>12}{limit_text:>12}{percent:>9}",
+            budget_name(section),
+            format!("{used:#010x}")
+        );
+    }
+    out
+}
+
+fn budget_used(section: Option<SectionKind>, layout: &LayoutPlan) -> u32 {
+    match section {
+        Some(section) => super::section_total(layout, section),
+        None => REPORT_SECTIONS.iter().fold(0u32, |acc, &section| {
+            acc.saturating_add(super::section_total(layout, section))
+        }),
+    }
+}
+
+fn budget_name(section: Option<SectionKind>) -> &'static str {
+    section.map_or("total", super::section_tag)
+}
+
+/// Percentage with one decimal, without going through floats.
+fn percent_text(used: u32, limit: u32) -> String {
+    if limit == 0 {
+        return if used == 0 { "0.0%" } else { "inf%" }.to_string();
+    }
+    let tenths = u64::from(used) * 1000 / u64::from(limit);
+    format!("{}.{}%", tenths / 10, tenths % 10)
+}
@@ -0,0 +1,109 @@
+use anyhow::{bail, Result};
+
+use crate::format::obj::{Command, ObjectFile};
+use crate::layout::LayoutPlan;
+use crate::resolver::SectionKind;
+use crate::target::Cpu;
+
+use super::map::display_obj_name;
+use super::{
+    code_hi, opaque_write_size, opcode, section_tag, section_total, usize_to_u32_saturating,
+    walk_commands,
+};
+
+/// Checks linker-written words and longs against `cpu`'s alignment rules.
+///
+/// Odd word/long writes and 32-bit `(d32,An)`/`(d32,PC)` displacements fail
+/// on the 68000. On the 68020 and later, the returned warnings name sections
+/// with long data that are placed off a 4-byte boundary.
+///
+/// # Errors
+/// Returns every violation, one per line, when `cpu` cannot execute the output.
+pub fn check_cpu_rules(
+    cpu: Cpu,
+    objects: &[ObjectFile],
+    input_paths: &[String],
+    layout: &LayoutPlan,
+) -> Result<Vec<String>> {
+    let mut errors = Vec::new();
+    let mut warnings = Vec::new();
+    let text_size = section_total(layout, SectionKind::Text);
+    for (idx, obj) in objects.iter().enumerate() {
+        let name = display_obj_name(input_paths.get(idx).map(String::as_str), idx);
+        let placement = layout.placements.get(idx).map(|p| &p.by_section);
+        // Data follows text in the image; other sections are checked from their own start.
+        let placed = |section: SectionKind| {
+            let base = if section == SectionKind::Data {
+                text_size
+            } else {
+                0
+            };
+            base.saturating_add(
+                placement
+                    .and_then(|p| p.get(&section).copied())
+                    .unwrap_or(0),
+            )
+        };
+        let mut long_sections = Vec::<SectionKind>::new();
+        // The last raw word written, as (section, offset just past it, word).
+        let mut last_word = None::<(SectionKind, u32, u16)>;
+        walk_commands(obj, |cmd, current, local, _calc_stack| {
+            let Command::Opaque { code, .. } = cmd else {
+                if let Command::RawData(bytes) = cmd {
+                    let end = local.saturating_add(usize_to_u32_saturating(bytes.len()));
+                    last_word = match bytes.as_slice() {
+                        [.., hi, lo] => Some((current, end, u16::from_be_bytes([*hi, *lo]))),
+                        _ => None,
+                    };
+                }
+                return;
+            };
+            let size = opaque_write_size(*code);
+            let addr = placed(current).saturating_add(local);
+            let at = format!("{} {addr:#x}", section_tag(current));
+            let after_extension = last_word.is_some_and(|(section, end, word)| {
+                section == current && end == local && is_long_base_displacement(word)
+            });
+            if code_hi(*code) == opcode::OPH_DISP_LONG
+                && after_extension
+                && !cpu.has_long_displacement()
+            {
+                errors.push(format!(
+                    "32-bit displacement at {at} needs a 68020 or later (--cpu) in {name}"
+                ));
+            }
+            if size >= 2 && addr & 1 != 0 && cpu.requires_even_access() {
+                errors.push(format!(
+                    "{size}-byte write at odd address {at} traps on the {} in {name}",
+                    cpu.name()
+                ));
+            }
+            if size == 4 && !long_sections.contains(&current) {
+                long_sections.push(current);
+            }
+        });
+        if !cpu.prefers_long_alignment() {
+            continue;
+        }
+        for section in long_sections {
+            let start = placed(section);
+            if start % 4 != 0 {
+                warnings.push(format!(
+                    "{} of {name} starts at {start:#x}; its longs are misaligned on the {} (try -e 4)",
+                    section_tag(section),
+                    cpu.name()
+                ));
+            }
+        }
+    }
+    if !errors.is_empty() {
+        bail!("{}", errors.join("\n"));
+    }
+    Ok(warnings)
+}
+
+/// A full-format extension word (bit 8 set) whose base displacement size
+/// (bits 5-4) is long; `dc.l sym-*` data has no such word in front of it.
+fn is_long_base_displacement(word: u16) -> bool {
+    word & 0x0100 != 0 && word & 0x0030 == 0x0030
+}
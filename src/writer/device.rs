@@ -0,0 +1,99 @@
+use thiserror::Error;
+
+/// next(4) + attribute(2) + strategy(4) + interrupt(4) + name(8)
+const DEVICE_HEADER_SIZE: usize = 22;
+const END_OF_CHAIN: u32 = 0xffff_ffff;
+const ATTR_CHARACTER: u16 = 0x8000;
+
+#[derive(Debug, Clone, PartialEq, Eq, Error)]
+pub(super) enum DeviceHeaderError {
+    #[error("device driver must start at the device header (text offset 0), but start address is {exec:#x}")]
+    StartAddress { exec: u32 },
+    #[error("device header at text+{offset:#x} needs 22 bytes, but text is {text_size:#x} bytes")]
+    Truncated { offset: usize, text_size: usize },
+    #[error("device header at text+{offset:#x}: next pointer {value:#010x} must be -1 or the relocated address of a later header")]
+    NextPointer { offset: usize, value: u32 },
+    #[error("device header at text+{offset:#x}: {field} entry {value:#x} is not a relocated even address inside text")]
+    Entry {
+        offset: usize,
+        field: &'static str,
+        value: u32,
+    },
+    #[error(
+        "device header at text+{offset:#x}: character device name must be 8 printable ASCII bytes"
+    )]
+    CharacterName { offset: usize },
+    #[error("device header at text+{offset:#x}: block device must declare at least one unit")]
+    NoUnits { offset: usize },
+}
+
+/// Checks the device header chain at the start of a linked driver text.
+///
+/// `relocations` are image offsets of relocated longs; strategy, interrupt and
+/// chained next pointers must be among them so Human68k rebases them on load.
+pub(super) fn validate_device_header(
+    text: &[u8],
+    relocations: &[u32],
+    exec: u32,
+) -> Result<(), DeviceHeaderError> {
+    if exec != 0 {
+        return Err(DeviceHeaderError::StartAddress { exec });
+    }
+    let relocated =
+        |at: usize| u32::try_from(at).is_ok_and(|at| relocations.binary_search(&at).is_ok());
+    let in_text = |value: u32| {
+        usize::try_from(value).is_ok_and(|v| v < text.len()) && value.is_multiple_of(2)
+    };
+
+    let mut offset = 0usize;
+    loop {
+        let Some(header) = text.get(offset..offset + DEVICE_HEADER_SIZE) else {
+            return Err(DeviceHeaderError::Truncated {
+                offset,
+                text_size: text.len(),
+            });
+        };
+        let next = read_u32(header, 0);
+        let attribute = u16::from_be_bytes([header[4], header[5]]);
+        for (field, at) in [("strategy", 6usize), ("interrupt", 10usize)] {
+            let value = read_u32(header, at);
+            if !relocated(offset + at) || !in_text(value) {
+                return Err(DeviceHeaderError::Entry {
+                    offset,
+                    field,
+                    value,
+                });
+            }
+        }
+        let name = &header[14..22];
+        if attribute & ATTR_CHARACTER != 0 {
+            if !name.iter().all(|&b| (0x20..=0x7e).contains(&b)) || name[0] == b' ' {
+                return Err(DeviceHeaderError::CharacterName { offset });
+            }
+        } else if name[0] == 0 {
+            return Err(DeviceHeaderError::NoUnits { offset });
+        }
+
+        if next == END_OF_CHAIN {
+            return Ok(());
+        }
+        // Forward-only links keep a malformed chain from looping.
+        let Ok(next_offset) = usize::try_from(next) else {
+            return Err(DeviceHeaderError::NextPointer {
+                offset,
+                value: next,
+            });
+        };
+        if !relocated(offset) || !in_text(next) || next_offset <= offset {
+            return Err(DeviceHeaderError::NextPointer {
+                offset,
+                value: next,
+            });
+        }
+        offset = next_offset;
+    }
+}
+
+fn read_u32(bytes: &[u8], at: usize) -> u32 {
+    u32::from_be_bytes([bytes[at], bytes[at + 1], bytes[at + 2], bytes[at + 3]])
+}
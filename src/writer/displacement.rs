@@ -0,0 +1,59 @@
+use anyhow::{bail, Result};
+
+use crate::format::obj::{Command, ObjectFile};
+use crate::layout::LayoutPlan;
+use crate::resolver::{ObjectSummary, SectionKind};
+
+use super::map::display_obj_name;
+use super::{
+    base_relative_placement, build_global_symbol_addrs, code_hi, data_image_size, opcode,
+    push_expr_diagnostic, resolve_opaque_value, section_total, walk_commands,
+};
+
+/// Fails on 8-bit PC-relative displacements (`6b`) that cannot reach their
+/// target after layout; the 16-bit ones are `islands::check_word_displacements`.
+pub(super) fn check_byte_displacements(
+    objects: &[ObjectFile],
+    input_paths: &[String],
+    summaries: &[ObjectSummary],
+    layout: &LayoutPlan,
+) -> Result<()> {
+    let addrs = build_global_symbol_addrs(
+        summaries,
+        layout,
+        section_total(layout, SectionKind::Text),
+        data_image_size(layout),
+        section_total(layout, SectionKind::Bss),
+        section_total(layout, SectionKind::Common),
+    )?;
+    let mut diagnostics = Vec::new();
+    for (idx, (obj, summary)) in objects.iter().zip(summaries).enumerate() {
+        let placement = base_relative_placement(layout, idx)?;
+        let name = display_obj_name(input_paths.get(idx).map(String::as_str), idx);
+        walk_commands(obj, |cmd, current, local, _calc_stack| {
+            let Command::Opaque { code, payload } = cmd else {
+                return;
+            };
+            if code_hi(*code) != opcode::OPH_DISP_BYTE {
+                return;
+            }
+            let Some(disp) = resolve_opaque_value(*code, payload, summary, &addrs, &placement)
+            else {
+                return;
+            };
+            if !(-0x80..=0x7f).contains(&disp) {
+                push_expr_diagnostic(
+                    &mut diagnostics,
+                    "バイトサイズ(-$80〜$7f)で表現できない値",
+                    &name,
+                    local,
+                    current,
+                );
+            }
+        });
+    }
+    if diagnostics.is_empty() {
+        return Ok(());
+    }
+    bail!("{}", diagnostics.join("\n"));
+}
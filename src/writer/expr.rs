@@ -37,17 +37,19 @@ pub(super) fn classify_expression_errors(
         }
         opcode::OPH_WRT_STK_BYTE_RAW => evaluate_wrt_stk_9300(calc_stack),
         opcode::OPH_WRT_STK_WORD_RELOC => evaluate_wrt_stk_9900(calc_stack, current),
-        opcode::OPH_ABS_WORD | opcode::OPH_ABS_BYTE => {
+        opcode::OPH_ABS_WORD | opcode::OPH_ABS_BYTE | opcode::OPH_XREF_BYTE => {
             evaluate_direct_byte(lo, payload, summary, global_symbols)
         }
-        opcode::OPH_ADD_WORD | opcode::OPH_ADD_BYTE => {
+        opcode::OPH_ADD_WORD | opcode::OPH_ADD_BYTE | opcode::OPH_ADD_XREF_BYTE => {
             evaluate_direct_byte_with_offset(lo, payload, summary, global_symbols)
         }
-        opcode::OPH_ABS_WORD_ALT => evaluate_direct_word(lo, payload, summary, global_symbols, current),
-        opcode::OPH_ADD_WORD_ALT => {
+        opcode::OPH_ABS_WORD_ALT | opcode::OPH_XREF_WORD => {
+            evaluate_direct_word(lo, payload, summary, global_symbols, current)
+        }
+        opcode::OPH_ADD_WORD_ALT | opcode::OPH_ADD_XREF_WORD => {
             evaluate_direct_word_with_offset(lo, payload, summary, global_symbols, current)
         }
-        opcode::OPH_DISP_WORD => evaluate_rel_word(payload, summary, global_symbols),
+        opcode::OPH_DISP_WORD | opcode::OPH_DISP_WORD_ALIAS => evaluate_rel_word(payload, summary, global_symbols),
         opcode::OPH_DISP_LONG => evaluate_d32_adrs(payload, summary, global_symbols),
         opcode::OPH_DISP_BYTE => evaluate_rel_byte(payload, summary, global_symbols),
         _ => Vec::new(),
@@ -85,11 +87,12 @@ fn evaluate_push_80(
 pub(super) fn evaluate_a0(lo: u8, calc_stack: &mut Vec<ExprEntry>) -> Vec<&'static str> {
     const STACK_UNDERFLOW: &str = "計算用スタックに値がありません";
     match lo {
+        // Unary plus keeps both value and attribute, so unlike the other unary
+        // operators it accepts an address; HLK only checks that an operand exists.
         0x02 => {
-            let Some(entry) = calc_stack.pop() else {
+            if calc_stack.is_empty() {
                 return vec![STACK_UNDERFLOW];
-            };
-            calc_stack.push(entry);
+            }
             Vec::new()
         }
         0x01 | 0x03 | 0x04 | 0x05 | 0x06 | 0x07 => {
@@ -557,7 +560,8 @@ fn evaluate_rel_word(
             return vec!["アドレス属性シンボルの値をワードサイズで出力"];
         }
     }
-    vec!["ワードサイズ(-$8000〜$7fff)で表現できない値"]
+    // The range depends on the final layout; see `islands::check_word_displacements`.
+    Vec::new()
 }
 
 fn evaluate_rel_byte(
@@ -573,7 +577,8 @@ fn evaluate_rel_byte(
             return vec!["アドレス属性シンボルの値をバイトサイズで出力"];
         }
     }
-    vec!["バイトサイズ(-$80〜$7f)で表現できない値"]
+    // Likewise; see `displacement::check_byte_displacements`.
+    Vec::new()
 }
 
 fn evaluate_d32_adrs(
@@ -0,0 +1,228 @@
+use std::collections::BTreeMap;
+
+use anyhow::{bail, Result};
+
+use crate::format::obj::{Command, ObjectFile};
+use crate::layout::{plan_layout, LayoutPlan};
+use crate::resolver::{ObjectSummary, SectionKind};
+
+use super::map::display_obj_name;
+use super::{
+    base_relative_placement, build_global_symbol_addrs, code_hi, code_lo, data_image_size,
+    opaque_write_size, opcode, push_expr_diagnostic, read_i32_be, read_u16_be,
+    resolve_opaque_value, section_total, usize_to_u32_saturating, walk_commands,
+};
+
+/// `jmp abs.l`; the target address long follows it.
+const JMP_ABS_LONG: [u8; 2] = [0x4e, 0xf9];
+const STUB_SIZE: u32 = 6;
+/// Each pass can push later branches out of range, so islands are added until the layout settles.
+const MAX_ISLAND_PASSES: usize = 8;
+
+/// A 16-bit displacement that does not reach its target once laid out.
+struct WordOverflow {
+    object: usize,
+    section: SectionKind,
+    local: u32,
+    command: usize,
+}
+
+fn find_word_overflows(
+    objects: &[ObjectFile],
+    summaries: &[ObjectSummary],
+    layout: &LayoutPlan,
+) -> Result<Vec<WordOverflow>> {
+    let addrs = build_global_symbol_addrs(
+        summaries,
+        layout,
+        section_total(layout, SectionKind::Text),
+        data_image_size(layout),
+        section_total(layout, SectionKind::Bss),
+        section_total(layout, SectionKind::Common),
+    )?;
+    let mut out = Vec::new();
+    for (idx, (obj, summary)) in objects.iter().zip(summaries).enumerate() {
+        let placement = base_relative_placement(layout, idx)?;
+        let mut command = 0usize;
+        walk_commands(obj, |cmd, current, local, _calc_stack| {
+            command += 1;
+            let Command::Opaque { code, payload } = cmd else {
+                return;
+            };
+            if !matches!(
+                code_hi(*code),
+                opcode::OPH_DISP_WORD | opcode::OPH_DISP_WORD_ALIAS
+            ) {
+                return;
+            }
+            let Some(disp) = resolve_opaque_value(*code, payload, summary, &addrs, &placement)
+            else {
+                return;
+            };
+            if !(-0x8000..=0x7fff).contains(&disp) {
+                out.push(WordOverflow {
+                    object: idx,
+                    section: current,
+                    local,
+                    command: command - 1,
+                });
+            }
+        });
+    }
+    Ok(out)
+}
+
+/// Fails on 16-bit displacements that cannot reach their target after layout.
+pub(super) fn check_word_displacements(
+    objects: &[ObjectFile],
+    input_paths: &[String],
+    summaries: &[ObjectSummary],
+    layout: &LayoutPlan,
+) -> Result<()> {
+    let overflows = find_word_overflows(objects, summaries, layout)?;
+    if overflows.is_empty() {
+        return Ok(());
+    }
+    let mut diagnostics = Vec::new();
+    for overflow in &overflows {
+        let name = display_obj_name(
+            input_paths.get(overflow.object).map(String::as_str),
+            overflow.object,
+        );
+        push_expr_diagnostic(
+            &mut diagnostics,
+            "ワードサイズ(-$8000〜$7fff)で表現できない値",
+            &name,
+            overflow.local,
+            overflow.section,
+        );
+    }
+    bail!("{}", diagnostics.join("\n"));
+}
+
+/// `--branch-islands`: retargets out-of-range 16-bit text displacements to a
+/// `jmp abs.l` stub appended to the branching object's text, one per target.
+///
+/// Returns the number of stubs added. Overflows that cannot be fixed this way
+/// are left for `check_word_displacements` to report.
+///
+/// # Errors
+/// Returns an error when a stub would itself be out of 16-bit range.
+pub fn insert_branch_islands(
+    objects: &mut [ObjectFile],
+    summaries: &mut [ObjectSummary],
+) -> Result<usize> {
+    let mut inserted = 0;
+    for _ in 0..MAX_ISLAND_PASSES {
+        let layout = plan_layout(summaries);
+        let mut by_object = BTreeMap::<usize, Vec<WordOverflow>>::new();
+        for overflow in find_word_overflows(objects, summaries, &layout)? {
+            if overflow.section == SectionKind::Text {
+                by_object.entry(overflow.object).or_default().push(overflow);
+            }
+        }
+        if by_object.is_empty() {
+            break;
+        }
+        for (idx, branches) in by_object {
+            inserted += add_island(&mut objects[idx], &mut summaries[idx], &branches)?;
+        }
+    }
+    Ok(inserted)
+}
+
+fn add_island(
+    obj: &mut ObjectFile,
+    summary: &mut ObjectSummary,
+    branches: &[WordOverflow],
+) -> Result<usize> {
+    let declared = summary
+        .declared_section_sizes
+        .get(&SectionKind::Text)
+        .copied()
+        .unwrap_or(0);
+    let mut written = 0u32;
+    walk_commands(obj, |cmd, current, local, _calc_stack| {
+        if current != SectionKind::Text {
+            return;
+        }
+        let size = match cmd {
+            Command::RawData(bytes) => usize_to_u32_saturating(bytes.len()),
+            Command::DefineSpace { size } => *size,
+            Command::Opaque { code, .. } => u32::from(opaque_write_size(*code)),
+            _ => 0,
+        };
+        written = written.max(local.saturating_add(size));
+    });
+    let start = (declared.max(written) + 1) & !1;
+
+    let mut labels = Vec::<u16>::new();
+    for branch in branches {
+        let Command::Opaque { code, payload } = &obj.commands[branch.command] else {
+            continue;
+        };
+        // Only displacements measured from this object's text can be redirected locally.
+        if code_lo(*code) != 0x01 {
+            continue;
+        }
+        let (Some(adr), Some(label)) = (
+            read_i32_be(payload),
+            read_u16_be(payload.get(4..).unwrap_or(&[])),
+        ) else {
+            continue;
+        };
+        let slot = labels.iter().position(|l| *l == label).unwrap_or_else(|| {
+            labels.push(label);
+            labels.len() - 1
+        });
+        let stub = start.saturating_add(usize_to_u32_saturating(slot).saturating_mul(STUB_SIZE));
+        let disp = i64::from(stub) - i64::from(adr);
+        let Ok(disp) = i16::try_from(disp) else {
+            bail!(
+                "branch island at text {stub:#x} is out of 16-bit range from {:#x}",
+                branch.local
+            );
+        };
+        obj.commands[branch.command] = Command::RawData(disp.to_be_bytes().to_vec());
+    }
+    if labels.is_empty() {
+        return Ok(0);
+    }
+
+    let mut island = vec![Command::ChangeSection { section: 0x01 }];
+    if start > written {
+        island.push(Command::RawData(vec![0; (start - written) as usize]));
+    }
+    for label in &labels {
+        island.push(Command::RawData(JMP_ABS_LONG.to_vec()));
+        island.push(Command::Opaque {
+            code: u16::from_be_bytes([opcode::OPH_ABS_LONG, 0xff]),
+            payload: label.to_be_bytes().to_vec(),
+        });
+    }
+    let at = obj
+        .commands
+        .iter()
+        .rposition(|cmd| matches!(cmd, Command::End))
+        .unwrap_or(obj.commands.len());
+    obj.commands.splice(at..at, island);
+
+    let end = start.saturating_add(usize_to_u32_saturating(labels.len()).saturating_mul(STUB_SIZE));
+    for cmd in &mut obj.commands {
+        if let Command::Header {
+            section: 0x01,
+            size,
+            ..
+        } = cmd
+        {
+            *size = end;
+        }
+    }
+    summary
+        .declared_section_sizes
+        .insert(SectionKind::Text, end);
+    summary
+        .observed_section_usage
+        .insert(SectionKind::Text, end);
+    Ok(labels.len())
+}
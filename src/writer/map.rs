@@ -4,7 +4,7 @@ use std::path::Path;
 
 use anyhow::{Context, Result};
 
-use crate::layout::LayoutPlan;
+use crate::layout::{LayoutPlan, RELATIVE_SECTION_ORDER};
 use crate::resolver::{ObjectSummary, SectionKind};
 
 /// Writes a CRLF-normalized map text file.
@@ -47,7 +47,7 @@ pub(crate) fn build_map_text(
     out.push_str("==========================================================\n");
     out.push_str(&format_exec_line(exec));
     let text_sz = super::section_total(layout, SectionKind::Text);
-    let data_sz = super::section_total(layout, SectionKind::Data);
+    let data_sz = super::data_image_size(layout);
     let bss_sz = super::section_total(layout, SectionKind::Bss);
     let common_sz = super::section_total(layout, SectionKind::Common);
     let stack_sz = super::section_total(layout, SectionKind::Stack);
@@ -62,20 +62,10 @@ pub(crate) fn build_map_text(
     cur = cur.saturating_add(common_sz);
     out.push_str(&format_section_line("stack", cur, stack_sz));
 
-    let mut rcur = 0u32;
-    for (name, kind) in [
-        ("rdata", SectionKind::RData),
-        ("rbss", SectionKind::RBss),
-        ("rcommon", SectionKind::RCommon),
-        ("rstack", SectionKind::RStack),
-        ("rldata", SectionKind::RLData),
-        ("rlbss", SectionKind::RLBss),
-        ("rlcommon", SectionKind::RLCommon),
-        ("rlstack", SectionKind::RLStack),
-    ] {
+    for kind in RELATIVE_SECTION_ORDER {
+        let start = layout.relative_section_start(kind).unwrap_or(0);
         let sz = super::section_total(layout, kind);
-        out.push_str(&format_section_line(name, rcur, sz));
-        rcur = rcur.saturating_add(sz);
+        out.push_str(&format_section_line(super::section_tag(kind), start, sz));
     }
 
     let def_owner = build_definition_owner_map(summaries);
@@ -90,22 +80,7 @@ pub(crate) fn build_map_text(
         out.push_str("==========================================================\n");
         out.push_str(&format_align_line(summary.object_align));
 
-        let placement = layout.placements.get(idx).map(|p| &p.by_section);
-        for (name, kind) in [
-            ("text", SectionKind::Text),
-            ("data", SectionKind::Data),
-            ("bss", SectionKind::Bss),
-            ("stack", SectionKind::Stack),
-        ] {
-            let pos = placement.and_then(|p| p.get(&kind).copied()).unwrap_or(0);
-            let size = summary
-                .declared_section_sizes
-                .get(&kind)
-                .copied()
-                .or_else(|| summary.observed_section_usage.get(&kind).copied())
-                .unwrap_or(0);
-            out.push_str(&format_section_line(name, pos, size));
-        }
+        out.push_str(&format_object_sections(summary, layout, idx));
 
         if !summary.xrefs.is_empty() {
             out.push_str("-------------------------- xref --------------------------\n");
@@ -192,7 +167,7 @@ fn build_definition_owner_map(summaries: &[ObjectSummary]) -> HashMap<Vec<u8>, u
     out
 }
 
-fn display_obj_name(path: Option<&str>, idx: usize) -> String {
+pub(super) fn display_obj_name(path: Option<&str>, idx: usize) -> String {
     if let Some(p) = path {
         return Path::new(p)
             .file_name()
@@ -219,6 +194,40 @@ fn format_align_line(align: u32) -> String {
     out
 }
 
+/// Section lines of one object; r-sections only when present, at their
+/// base-register offset.
+fn format_object_sections(summary: &ObjectSummary, layout: &LayoutPlan, idx: usize) -> String {
+    let placement = layout.placements.get(idx).map(|p| &p.by_section);
+    let size_of = |kind: SectionKind| {
+        summary
+            .declared_section_sizes
+            .get(&kind)
+            .copied()
+            .or_else(|| summary.observed_section_usage.get(&kind).copied())
+            .unwrap_or(0)
+    };
+    let mut out = String::new();
+    for kind in [SectionKind::Text, SectionKind::Data, SectionKind::Bss, SectionKind::Stack] {
+        let pos = placement.and_then(|p| p.get(&kind).copied()).unwrap_or(0);
+        out.push_str(&format_section_line(super::section_tag(kind), pos, size_of(kind)));
+    }
+    for kind in [
+        SectionKind::RData,
+        SectionKind::RBss,
+        SectionKind::RStack,
+        SectionKind::RLData,
+        SectionKind::RLBss,
+        SectionKind::RLStack,
+    ] {
+        let Some(pos) = placement.and_then(|p| p.get(&kind).copied()) else {
+            continue;
+        };
+        let start = layout.relative_section_start(kind).unwrap_or(0);
+        out.push_str(&format_section_line(super::section_tag(kind), start.saturating_add(pos), size_of(kind)));
+    }
+    out
+}
+
 fn format_section_line(name: &str, pos: u32, size: u32) -> String {
     let mut label = format_label_prefix(name);
     if size == 0 {
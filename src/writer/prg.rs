@@ -0,0 +1,139 @@
+use std::path::Path;
+
+use anyhow::{bail, Result};
+
+use crate::format::obj::ObjectFile;
+use crate::layout::LayoutPlan;
+use crate::resolver::ObjectSummary;
+
+use super::{
+    build_global_symbol_addrs_with_g2lk, collect_symbol_entries, link_image, put_u32_be,
+    to_human68k_path, usize_to_u32_saturating, OutputOptions, SymbolSelection,
+};
+
+const PRG_MAGIC: [u8; 2] = [0x60, 0x1a];
+const PRG_HEADER_SIZE: usize = 28;
+
+// DRI symbol type bits.
+const DRI_DEFINED: u16 = 0x8000;
+const DRI_EQUATED: u16 = 0x4000;
+const DRI_GLOBAL: u16 = 0x2000;
+const DRI_DATA: u16 = 0x0400;
+const DRI_TEXT: u16 = 0x0200;
+const DRI_BSS: u16 = 0x0100;
+/// GST extension: the next 14-byte record carries name bytes 8..22.
+const DRI_LONG_NAME: u16 = 0x0048;
+
+const DRI_NAME_SIZE: usize = 8;
+const DRI_EXT_NAME_SIZE: usize = 14;
+
+/// Builds an Atari TOS `.PRG`: `0x601A` header, text, data, DRI symbol table
+/// and the TOS fixup list.
+pub(super) fn build_prg_image(
+    output_path: &str,
+    options: &OutputOptions,
+    objects: &[ObjectFile],
+    summaries: &[ObjectSummary],
+    layout: &LayoutPlan,
+) -> Result<Vec<u8>> {
+    let linked = link_image(objects, summaries, layout, options.g2lk_mode)?;
+    if linked.exec != 0 {
+        bail!(
+            "実行開始アドレスがファイル先頭ではありません: {}",
+            to_human68k_path(Path::new(output_path))
+        );
+    }
+    // A fixup list starting with offset 0 reads as "no fixups" to TOS.
+    if linked.relocations.first() == Some(&0) {
+        bail!(
+            "relocated long at text offset 0 cannot be a TOS fixup: {}",
+            to_human68k_path(Path::new(output_path))
+        );
+    }
+    let selection = SymbolSelection::new(options.symbol_table, &options.symbol_filter);
+    let symbols = if selection.writes_table() {
+        let global_symbol_addrs = build_global_symbol_addrs_with_g2lk(
+            summaries,
+            layout,
+            linked.text_size,
+            linked.data_size,
+            linked.bss_only,
+            linked.common_only,
+            linked.g2lk_synth,
+        )?;
+        let mut out = Vec::new();
+        for (ty, addr, name) in collect_symbol_entries(selection, summaries, layout, &linked)? {
+            // X tables record common symbols by size; TOS wants their bss address.
+            let addr = if ty == 0x0003 {
+                global_symbol_addrs.get(name).map_or(addr, |sym| sym.addr)
+            } else {
+                addr
+            };
+            append_dri_symbol(&mut out, dri_type(ty), addr, name);
+        }
+        out
+    } else {
+        Vec::new()
+    };
+
+    let mut image = vec![0u8; PRG_HEADER_SIZE];
+    image[0..2].copy_from_slice(&PRG_MAGIC);
+    put_u32_be(&mut image, 2, linked.text_size);
+    put_u32_be(&mut image, 6, linked.data_size);
+    put_u32_be(&mut image, 10, linked.bss_size);
+    put_u32_be(&mut image, 14, usize_to_u32_saturating(symbols.len()));
+    // reserved, prgflags and absflag (0 = fixups follow) stay zero.
+    image.extend_from_slice(&linked.text);
+    image.extend_from_slice(&linked.data);
+    image.extend_from_slice(&symbols);
+    image.extend_from_slice(&encode_tos_fixups(&linked.relocations));
+    Ok(image)
+}
+
+/// Encodes sorted image offsets as TOS fixups: the first offset as a long, then
+/// byte deltas where 1 advances 254 bytes without patching, ending with 0.
+pub(super) fn encode_tos_fixups(offsets: &[u32]) -> Vec<u8> {
+    let Some((&first, rest)) = offsets.split_first() else {
+        return vec![0, 0, 0, 0];
+    };
+    let mut out = first.to_be_bytes().to_vec();
+    let mut prev = first;
+    for &off in rest {
+        let mut delta = off - prev;
+        while delta > 254 {
+            out.push(1);
+            delta -= 254;
+        }
+        out.push(u8::try_from(delta).unwrap_or(0));
+        prev = off;
+    }
+    out.push(0);
+    out
+}
+
+fn dri_type(x_type: u16) -> u16 {
+    match x_type {
+        0x0201 => DRI_DEFINED | DRI_GLOBAL | DRI_TEXT,
+        0x0202 => DRI_DEFINED | DRI_GLOBAL | DRI_DATA,
+        0x0203 | 0x0204 | 0x0003 => DRI_DEFINED | DRI_GLOBAL | DRI_BSS,
+        _ => DRI_DEFINED | DRI_GLOBAL | DRI_EQUATED,
+    }
+}
+
+fn append_dri_symbol(out: &mut Vec<u8>, ty: u16, value: u32, name: &[u8]) {
+    let (head, tail) = name.split_at(name.len().min(DRI_NAME_SIZE));
+    let ty = if tail.is_empty() {
+        ty
+    } else {
+        ty | DRI_LONG_NAME
+    };
+    out.extend_from_slice(head);
+    out.resize(out.len() + DRI_NAME_SIZE - head.len(), 0);
+    out.extend_from_slice(&ty.to_be_bytes());
+    out.extend_from_slice(&value.to_be_bytes());
+    if !tail.is_empty() {
+        let tail = &tail[..tail.len().min(DRI_EXT_NAME_SIZE)];
+        out.extend_from_slice(tail);
+        out.resize(out.len() + DRI_EXT_NAME_SIZE - tail.len(), 0);
+    }
+}
@@ -0,0 +1,136 @@
+use crate::format::obj::ObjectFile;
+use crate::resolver::{ObjectSummary, SectionKind};
+
+use super::map::display_obj_name;
+use super::read_u32_be;
+
+const SCD_HEADER_SIZE: usize = 12;
+const LINE_ENTRY_SIZE: usize = 6;
+const INFO_ENTRY_SIZE: usize = 18;
+
+/// A malformed SCD tail; `object` indexes the input whose debug info it belongs to.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ScdProblem {
+    pub object: usize,
+    pub message: String,
+}
+
+/// Checks each object's SCD tail before it is rebased into the output.
+///
+/// Covers the header sizes, line entries against the object's text, and the
+/// sinfo count, einfo sinfo references and ninfo name offsets. Section and
+/// common-reference errors are still reported while rebasing, as HLK does.
+#[must_use]
+pub fn validate_scd(
+    objects: &[ObjectFile],
+    input_paths: &[String],
+    summaries: &[ObjectSummary],
+) -> Vec<ScdProblem> {
+    let mut problems = Vec::new();
+    for (idx, obj) in objects.iter().enumerate() {
+        if obj.scd_tail.is_empty() {
+            continue;
+        }
+        let name = display_obj_name(input_paths.get(idx).map(String::as_str), idx);
+        let text_size = summaries
+            .get(idx)
+            .and_then(|s| s.declared_section_sizes.get(&SectionKind::Text).copied())
+            .unwrap_or(0);
+        for message in check_tail(&obj.scd_tail, text_size) {
+            problems.push(ScdProblem {
+                object: idx,
+                message: format!("{message} in {name}"),
+            });
+        }
+    }
+    problems
+}
+
+#[allow(clippy::similar_names)]
+fn check_tail(tail: &[u8], text_size: u32) -> Vec<String> {
+    if tail.len() < SCD_HEADER_SIZE {
+        return vec![format!("SCD header is truncated ({} bytes)", tail.len())];
+    }
+    let size_at = |at: usize| read_u32_be(&tail[at..]).map_or(0, |v| v as usize);
+    let (linfo_size, info_size, ninfo_size) = (size_at(0), size_at(4), size_at(8));
+    let total = linfo_size
+        .saturating_add(info_size)
+        .saturating_add(ninfo_size);
+    let available = tail.len() - SCD_HEADER_SIZE;
+    if total > available {
+        return vec![format!(
+            "SCD tables need {total} bytes but only {available} follow the header"
+        )];
+    }
+
+    let mut out = Vec::new();
+    if !linfo_size.is_multiple_of(LINE_ENTRY_SIZE) {
+        out.push(format!(
+            "SCD line table size {linfo_size} is not a multiple of {LINE_ENTRY_SIZE}"
+        ));
+    }
+    if !info_size.is_multiple_of(INFO_ENTRY_SIZE) {
+        out.push(format!(
+            "SCD info table size {info_size} is not a multiple of {INFO_ENTRY_SIZE}"
+        ));
+    }
+    if !out.is_empty() {
+        return out;
+    }
+
+    let linfo = &tail[SCD_HEADER_SIZE..SCD_HEADER_SIZE + linfo_size];
+    let info = &tail[SCD_HEADER_SIZE + linfo_size..SCD_HEADER_SIZE + linfo_size + info_size];
+    let ninfo = &tail[SCD_HEADER_SIZE + linfo_size + info_size..][..ninfo_size];
+
+    for (i, entry) in linfo.chunks_exact(LINE_ENTRY_SIZE).enumerate() {
+        let loc = read_u32_be(entry).unwrap_or(0);
+        if loc > text_size {
+            out.push(format!(
+                "SCD line entry #{i} points at text {loc:#x}, past the {text_size:#x}-byte text"
+            ));
+        }
+    }
+
+    let entries = info.len() / INFO_ENTRY_SIZE;
+    // The sinfo count is carried in the value of the first (.file) entry.
+    let sinfo_count = info.get(8..).and_then(read_u32_be).unwrap_or(0);
+    if sinfo_count as usize > entries {
+        out.push(format!(
+            "SCD sinfo count {sinfo_count} exceeds the {entries} entries of the info table"
+        ));
+        return out;
+    }
+    for (i, entry) in info.chunks_exact(INFO_ENTRY_SIZE).enumerate() {
+        let head = read_u32_be(entry).unwrap_or(0);
+        let second = read_u32_be(&entry[4..]).unwrap_or(0);
+        if i < sinfo_count as usize {
+            if head == 0 {
+                if let Some(problem) = check_ninfo_name(ninfo, second) {
+                    out.push(format!("SCD sinfo entry #{i} {problem}"));
+                }
+            }
+        } else if head == 0 && second > sinfo_count {
+            out.push(format!(
+                "SCD einfo entry #{} refers to sinfo #{second} but only {sinfo_count} exist",
+                i - sinfo_count as usize
+            ));
+        }
+    }
+    out
+}
+
+fn check_ninfo_name(ninfo: &[u8], offset: u32) -> Option<String> {
+    let Some(rest) = ninfo.get(offset as usize..).filter(|rest| !rest.is_empty()) else {
+        return Some(format!(
+            "names ninfo offset {offset:#x} outside the {}-byte name table",
+            ninfo.len()
+        ));
+    };
+    if rest.contains(&0) {
+        None
+    } else {
+        Some(format!(
+            "names an unterminated string at ninfo offset {offset:#x}"
+        ))
+    }
+}
@@ -0,0 +1,119 @@
+/// 68000 fix-up stub placed in front of a self-relocating `.r`.
+///
+/// `done` makes a second entry (a program restarting itself in place) skip
+/// the fix-ups. The 68040 and 68060 do not snoop their instruction caches,
+/// so after patching, the stub reads the MPU type the IPL leaves at `$cbc`
+/// and flushes the caches with IOCS `_SYS_STAT` there; older ROMs may lack
+/// that call, which is why it is not made on a 68000-68030.
+///
+/// ```text
+///        subq.l  #4,sp                 ; slot for the entry address
+///        movem.l d0-d1/a0-a2,-(sp)
+///        lea     table(pc),a1
+///        movea.l a1,a0
+///        adda.l  #TABLE_LEN,a0         ; a0 = program start
+///        lea     done(pc),a2
+///        tst.b   (a2)
+///        bne.s   jump                  ; already relocated
+///        st      (a2)
+///        move.l  a0,d0
+///        movea.l a0,a2
+/// loop:  moveq   #0,d1
+///        move.w  (a1)+,d1              ; delta to next long, 0 = end
+///        beq.s   flush
+///        cmpi.w  #1,d1                 ; 1 = long delta follows
+///        bne.s   add
+///        move.l  (a1)+,d1
+/// add:   adda.l  d1,a2
+///        add.l   d0,(a2)
+///        bra.s   loop
+/// flush: lea     $0cbc.w,a1            ; MPU type
+///        moveq   #_B_BPEEK,d0
+///        trap    #15
+///        cmpi.b  #4,d0
+///        bcs.s   jump
+///        moveq   #3,d1                 ; flush caches
+///        moveq   #_SYS_STAT,d0
+///        trap    #15
+/// jump:  adda.l  #EXEC,a0
+///        move.l  a0,20(sp)
+///        movem.l (sp)+,d0-d1/a0-a2
+///        rts                           ; jump to the real entry
+/// done:  dc.w    0
+/// table:
+/// ```
+const STUB: [u8; 90] = [
+    0x59, 0x8f, // subq.l #4,sp
+    0x48, 0xe7, 0xc0, 0xe0, // movem.l d0-d1/a0-a2,-(sp)
+    0x43, 0xfa, 0x00, 0x52, // lea table(pc),a1
+    0x20, 0x49, // movea.l a1,a0
+    0xd1, 0xfc, 0x00, 0x00, 0x00, 0x00, // adda.l #TABLE_LEN,a0
+    0x45, 0xfa, 0x00, 0x44, // lea done(pc),a2
+    0x4a, 0x12, // tst.b (a2)
+    0x66, 0x2e, // bne.s jump
+    0x50, 0xd2, // st (a2)
+    0x20, 0x08, // move.l a0,d0
+    0x24, 0x48, // movea.l a0,a2
+    0x72, 0x00, // loop: moveq #0,d1
+    0x32, 0x19, // move.w (a1)+,d1
+    0x67, 0x0e, // beq.s flush
+    0x0c, 0x41, 0x00, 0x01, // cmpi.w #1,d1
+    0x66, 0x02, // bne.s add
+    0x22, 0x19, // move.l (a1)+,d1
+    0xd5, 0xc1, // add: adda.l d1,a2
+    0xd1, 0x92, // add.l d0,(a2)
+    0x60, 0xec, // bra.s loop
+    0x43, 0xf8, 0x0c, 0xbc, // flush: lea $0cbc.w,a1
+    0x70, 0x84, // moveq #_B_BPEEK,d0
+    0x4e, 0x4f, // trap #15
+    0x0c, 0x00, 0x00, 0x04, // cmpi.b #4,d0
+    0x65, 0x06, // bcs.s jump
+    0x72, 0x03, // moveq #3,d1
+    0x70, 0xac, // moveq #_SYS_STAT,d0
+    0x4e, 0x4f, // trap #15
+    0xd1, 0xfc, 0x00, 0x00, 0x00, 0x00, // jump: adda.l #EXEC,a0
+    0x2f, 0x48, 0x00, 0x14, // move.l a0,20(sp)
+    0x4c, 0xdf, 0x07, 0x03, // movem.l (sp)+,d0-d1/a0-a2
+    0x4e, 0x75, // rts
+    0x00, 0x00, // done: dc.w 0
+];
+const TABLE_LEN_AT: usize = 14;
+const EXEC_AT: usize = 74;
+const LONG_DELTA: u16 = 1;
+
+/// Prepends the fix-up stub and relocation table to a `.r` program image.
+///
+/// `relocations` are sorted offsets of longs in `program`; `exec` is the
+/// program-relative entry point the stub jumps to after fixing them up.
+pub(super) fn prepend_relocation_stub(program: &[u8], relocations: &[u32], exec: u32) -> Vec<u8> {
+    let table = encode_relocation_deltas(relocations);
+    let table_len = u32::try_from(table.len()).unwrap_or(u32::MAX);
+    let mut out = STUB.to_vec();
+    out[TABLE_LEN_AT..TABLE_LEN_AT + 4].copy_from_slice(&table_len.to_be_bytes());
+    out[EXEC_AT..EXEC_AT + 4].copy_from_slice(&exec.to_be_bytes());
+    out.extend_from_slice(&table);
+    out.extend_from_slice(program);
+    out
+}
+
+/// Word deltas between relocated longs (the first from program start); a delta
+/// that does not fit a word is written as `1` plus a long. Ends with `0`.
+pub(super) fn encode_relocation_deltas(relocations: &[u32]) -> Vec<u8> {
+    let mut out = Vec::new();
+    let mut prev = 0u32;
+    for &off in relocations {
+        let delta = off - prev;
+        match u16::try_from(delta) {
+            Ok(word) if word != 0 && word != LONG_DELTA => {
+                out.extend_from_slice(&word.to_be_bytes());
+            }
+            _ => {
+                out.extend_from_slice(&LONG_DELTA.to_be_bytes());
+                out.extend_from_slice(&delta.to_be_bytes());
+            }
+        }
+        prev = off;
+    }
+    out.extend_from_slice(&[0, 0]);
+    out
+}
@@ -0,0 +1,33 @@
+use anyhow::Result;
+
+use crate::format::obj::ObjectFile;
+use crate::layout::LayoutPlan;
+use crate::resolver::ObjectSummary;
+
+use super::{
+    apply_x_header_options, assemble_x_image, LinkedImage, OutputOptions, SymbolSelection,
+};
+
+/// Builds the `--debug-file` companion of an `.r`/`.mcs` output.
+///
+/// The output's own link is written as an `.x` image, so its symbol table and
+/// rebased SCD tables are where X68000 debuggers expect them. The header base
+/// address is the output's load address (`-b`) plus `load_offset`, the size of
+/// anything placed in front of the program such as the self-relocation stub.
+pub(super) fn build_debug_sidecar(
+    options: &OutputOptions,
+    linked: &LinkedImage,
+    objects: &[ObjectFile],
+    summaries: &[ObjectSummary],
+    layout: &LayoutPlan,
+    load_offset: u32,
+) -> Result<Vec<u8>> {
+    let symbols = SymbolSelection::new(options.symbol_table, &options.symbol_filter);
+    let mut image = assemble_x_image(linked, objects, summaries, layout, symbols)?;
+    apply_x_header_options(
+        &mut image,
+        options.base_address.wrapping_add(load_offset),
+        0,
+    )?;
+    Ok(image)
+}
@@ -0,0 +1,466 @@
+use std::collections::{BTreeMap, HashSet};
+use std::fmt::Write as _;
+use std::path::Path;
+
+use anyhow::{bail, Context, Result};
+use serde_json::{json, Value};
+
+use crate::layout::LayoutPlan;
+use crate::resolver::{ObjectSummary, SectionKind};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SizeReportFormat {
+    Text,
+    Json,
+}
+
+pub(super) const REPORT_SECTIONS: [SectionKind; 13] = [
+    SectionKind::Text,
+    SectionKind::Data,
+    SectionKind::Bss,
+    SectionKind::Common,
+    SectionKind::Stack,
+    SectionKind::RData,
+    SectionKind::RBss,
+    SectionKind::RCommon,
+    SectionKind::RStack,
+    SectionKind::RLData,
+    SectionKind::RLBss,
+    SectionKind::RLCommon,
+    SectionKind::RLStack,
+];
+
+const OBJECT_SECTIONS: [SectionKind; 10] = [
+    SectionKind::Text,
+    SectionKind::Data,
+    SectionKind::Bss,
+    SectionKind::Stack,
+    SectionKind::RData,
+    SectionKind::RBss,
+    SectionKind::RStack,
+    SectionKind::RLData,
+    SectionKind::RLBss,
+    SectionKind::RLStack,
+];
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct SizeReport {
+    pub(crate) sections: Vec<(SectionKind, u32)>,
+    pub(crate) objects: Vec<ObjectSize>,
+    pub(crate) archives: Vec<(String, u32)>,
+    pub(crate) symbols: Vec<SymbolSize>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct ObjectSize {
+    pub(crate) name: String,
+    pub(crate) total: u32,
+    pub(crate) by_section: Vec<(SectionKind, u32)>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct SymbolSize {
+    pub(crate) name: String,
+    pub(crate) section: SectionKind,
+    pub(crate) addr: u32,
+    pub(crate) size: u32,
+    pub(crate) object: String,
+}
+
+/// Writes a per-object/per-archive/per-symbol size report.
+///
+/// `output_path` of `None` prints the report to stdout.
+///
+/// # Errors
+/// Returns an error when writing `output_path` fails.
+pub fn write_size_report(
+    exec_output_path: &str,
+    output_path: Option<&str>,
+    format: SizeReportFormat,
+    summaries: &[ObjectSummary],
+    layout: &LayoutPlan,
+    input_paths: &[String],
+) -> Result<()> {
+    let report = build_size_report(summaries, layout, input_paths)?;
+    let text = match format {
+        SizeReportFormat::Text => render_text(exec_output_path, &report),
+        SizeReportFormat::Json => render_json(exec_output_path, &report),
+    };
+    match output_path {
+        Some(path) => {
+            std::fs::write(path, text).with_context(|| format!("failed to write {path}"))?;
+        }
+        None => print!("{text}"),
+    }
+    Ok(())
+}
+
+pub(crate) fn build_size_report(
+    summaries: &[ObjectSummary],
+    layout: &LayoutPlan,
+    input_paths: &[String],
+) -> Result<SizeReport> {
+    let sections = REPORT_SECTIONS
+        .iter()
+        .map(|&kind| (kind, super::section_total(layout, kind)))
+        .collect::<Vec<_>>();
+
+    let mut objects = Vec::new();
+    let mut archive_totals = BTreeMap::<String, u32>::new();
+    for (idx, summary) in summaries.iter().enumerate() {
+        let label = input_paths.get(idx).map(String::as_str);
+        let by_section = OBJECT_SECTIONS
+            .iter()
+            .map(|&kind| (kind, super::section_size(summary, kind)))
+            .filter(|(_, size)| *size != 0)
+            .collect::<Vec<_>>();
+        let total = by_section
+            .iter()
+            .fold(0u32, |acc, (_, size)| acc.saturating_add(*size));
+        if let Some(archive) = label.and_then(archive_of) {
+            let entry = archive_totals.entry(archive).or_insert(0);
+            *entry = entry.saturating_add(total);
+        }
+        objects.push(ObjectSize {
+            name: report_obj_name(label, idx),
+            total,
+            by_section,
+        });
+    }
+    objects.sort_by(|a, b| b.total.cmp(&a.total).then_with(|| a.name.cmp(&b.name)));
+
+    let mut archives = archive_totals.into_iter().collect::<Vec<_>>();
+    archives.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+
+    let mut symbols = collect_symbol_sizes(summaries, layout, input_paths)?;
+    symbols.sort_by(|a, b| b.size.cmp(&a.size).then_with(|| a.name.cmp(&b.name)));
+
+    Ok(SizeReport {
+        sections,
+        objects,
+        archives,
+        symbols,
+    })
+}
+
+/// Objects and archives are named by their full input path, so same-named
+/// files from different directories stay apart in reports and diffs.
+fn report_obj_name(path: Option<&str>, idx: usize) -> String {
+    path.map_or_else(|| format!("obj{idx}"), str::to_string)
+}
+
+fn archive_of(label: &str) -> Option<String> {
+    let inner = label.strip_suffix(')')?;
+    let open = inner.rfind('(')?;
+    Some(inner[..open].to_string())
+}
+
+fn collect_symbol_sizes(
+    summaries: &[ObjectSummary],
+    layout: &LayoutPlan,
+    input_paths: &[String],
+) -> Result<Vec<SymbolSize>> {
+    let text_size = super::section_total(layout, SectionKind::Text);
+    let data_size = super::data_image_size(layout);
+    let bss_only = super::section_total(layout, SectionKind::Bss);
+    let common_only = super::section_total(layout, SectionKind::Common);
+    let stack_size = super::section_total(layout, SectionKind::Stack);
+    let common_offsets = super::build_scd_xdef_map(summaries);
+    let relative_start = |kind| layout.relative_section_start(kind).unwrap_or(0);
+
+    let mut seen = HashSet::<&[u8]>::new();
+    let mut by_section = BTreeMap::<SectionKind, Vec<(u32, String, String)>>::new();
+    for (idx, summary) in summaries.iter().enumerate() {
+        // Base-relative sections are sized at their offset from the base
+        // register, as the map file shows them.
+        let placement = super::base_relative_placement(layout, idx)?;
+        for sym in &summary.symbols {
+            if sym.name.first() == Some(&b'*') {
+                continue;
+            }
+            let common_at = |base: &[u32]| {
+                common_offsets
+                    .get(&sym.name)
+                    .filter(|x| x.section == sym.section)
+                    .map(|xdef| {
+                        let terms = [base, &[xdef.value]].concat();
+                        super::checked_address(&terms, || {
+                            format!("symbol {}", String::from_utf8_lossy(&sym.name))
+                        })
+                    })
+            };
+            let addr = match sym.section {
+                SectionKind::Text
+                | SectionKind::Data
+                | SectionKind::Bss
+                | SectionKind::Stack
+                | SectionKind::RData
+                | SectionKind::RBss
+                | SectionKind::RStack
+                | SectionKind::RLData
+                | SectionKind::RLBss
+                | SectionKind::RLStack => super::symbol_address(
+                    sym,
+                    &placement,
+                    text_size,
+                    data_size,
+                    bss_only,
+                    common_only,
+                )?,
+                SectionKind::Common => {
+                    let Some(addr) = common_at(&[text_size, data_size, bss_only]) else {
+                        continue;
+                    };
+                    addr?
+                }
+                SectionKind::RCommon | SectionKind::RLCommon => {
+                    let Some(addr) = common_at(&[relative_start(sym.section)]) else {
+                        continue;
+                    };
+                    addr?
+                }
+                _ => continue,
+            };
+            if !seen.insert(sym.name.as_slice()) {
+                continue;
+            }
+            by_section.entry(sym.section).or_default().push((
+                addr,
+                String::from_utf8_lossy(&sym.name).to_string(),
+                report_obj_name(input_paths.get(idx).map(String::as_str), idx),
+            ));
+        }
+    }
+
+    let data_end = text_size.saturating_add(data_size);
+    let bss_end = data_end.saturating_add(bss_only);
+    let common_end = bss_end.saturating_add(common_only);
+    Ok(size_by_next_symbol(by_section, |section| match section {
+        SectionKind::Text => text_size,
+        SectionKind::Data => data_end,
+        SectionKind::Bss => bss_end,
+        SectionKind::Common => common_end,
+        SectionKind::Stack => common_end.saturating_add(stack_size),
+        _ => relative_start(section).saturating_add(super::section_total(layout, section)),
+    }))
+}
+
+/// Sizes each symbol up to the next one in its section, the last up to `section_end`.
+fn size_by_next_symbol(
+    by_section: BTreeMap<SectionKind, Vec<(u32, String, String)>>,
+    section_end: impl Fn(SectionKind) -> u32,
+) -> Vec<SymbolSize> {
+    let mut out = Vec::new();
+    for (section, mut entries) in by_section {
+        let section_end = section_end(section);
+        entries.sort_by_key(|(addr, _, _)| *addr);
+        let next_addrs = entries
+            .iter()
+            .skip(1)
+            .map(|(addr, _, _)| *addr)
+            .chain(std::iter::once(section_end))
+            .collect::<Vec<_>>();
+        for ((addr, name, object), next) in entries.into_iter().zip(next_addrs) {
+            out.push(SymbolSize {
+                name,
+                section,
+                addr,
+                size: next.saturating_sub(addr),
+                object,
+            });
+        }
+    }
+    out
+}
+
+pub(crate) fn render_text(exec_output_path: &str, report: &SizeReport) -> String {
+    let mut out = String::new();
+    let _ = writeln!(out, "# rhlk size report: {exec_output_path}");
+    for (kind, size) in &report.sections {
+        let _ = writeln!(out, "section\t{size:08x}\t{}", super::section_tag(*kind));
+    }
+    for obj in &report.objects {
+        let detail = obj
+            .by_section
+            .iter()
+            .map(|(kind, size)| format!("{}={size:08x}", super::section_tag(*kind)))
+            .collect::<Vec<_>>()
+            .join(" ");
+        let _ = writeln!(out, "object\t{:08x}\t{}\t{detail}", obj.total, obj.name);
+    }
+    for (name, size) in &report.archives {
+        let _ = writeln!(out, "archive\t{size:08x}\t{name}");
+    }
+    for sym in &report.symbols {
+        let _ = writeln!(
+            out,
+            "symbol\t{:08x}\t{}\t{} {:08x} {}",
+            sym.size,
+            sym.name,
+            super::section_tag(sym.section),
+            sym.addr,
+            sym.object
+        );
+    }
+    out
+}
+
+pub(crate) fn render_json(exec_output_path: &str, report: &SizeReport) -> String {
+    let sections = report
+        .sections
+        .iter()
+        .map(|(kind, size)| json!({"name": super::section_tag(*kind), "size": size}))
+        .collect::<Vec<_>>();
+    let objects = report
+        .objects
+        .iter()
+        .map(|obj| {
+            let sections = obj
+                .by_section
+                .iter()
+                .map(|(kind, size)| (super::section_tag(*kind).to_string(), json!(size)))
+                .collect::<serde_json::Map<_, _>>();
+            json!({"name": obj.name, "size": obj.total, "sections": sections})
+        })
+        .collect::<Vec<_>>();
+    let archives = report
+        .archives
+        .iter()
+        .map(|(name, size)| json!({"name": name, "size": size}))
+        .collect::<Vec<_>>();
+    let symbols = report
+        .symbols
+        .iter()
+        .map(|sym| {
+            json!({
+                "name": sym.name,
+                "size": sym.size,
+                "section": super::section_tag(sym.section),
+                "address": sym.addr,
+                "object": sym.object,
+            })
+        })
+        .collect::<Vec<_>>();
+    let root = json!({
+        "output": exec_output_path,
+        "sections": sections,
+        "objects": objects,
+        "archives": archives,
+        "symbols": symbols,
+    });
+    let mut out = serde_json::to_string_pretty(&root).unwrap_or_default();
+    out.push('\n');
+    out
+}
+
+/// Compares two size reports (text or JSON) and lists entries whose size changed.
+///
+/// # Errors
+/// Returns an error when either report cannot be read or parsed.
+pub fn diff_size_reports(old_path: &Path, new_path: &Path) -> Result<String> {
+    let old = load_report_entries(old_path)?;
+    let new = load_report_entries(new_path)?;
+    Ok(diff_report_entries(&old, &new))
+}
+
+pub(crate) fn diff_report_entries(old: &ReportEntries, new: &ReportEntries) -> String {
+    let mut changes = Vec::<(i64, String)>::new();
+    for (key, &new_size) in new {
+        let old_size = old.get(key).copied();
+        let delta = i64::from(new_size) - i64::from(old_size.unwrap_or(0));
+        if old_size.is_some() && delta == 0 {
+            continue;
+        }
+        let line = match old_size {
+            Some(old_size) => format!(
+                "{}\t{}\t{old_size:08x} -> {new_size:08x} ({delta:+})",
+                key.0, key.1
+            ),
+            None => format!("{}\t{}\tnew {new_size:08x} ({delta:+})", key.0, key.1),
+        };
+        changes.push((delta, line));
+    }
+    for (key, &old_size) in old {
+        if new.contains_key(key) {
+            continue;
+        }
+        let delta = -i64::from(old_size);
+        changes.push((
+            delta,
+            format!("{}\t{}\tremoved {old_size:08x} ({delta:+})", key.0, key.1),
+        ));
+    }
+    changes.sort_by(|a, b| b.0.cmp(&a.0).then_with(|| a.1.cmp(&b.1)));
+
+    let mut out = String::new();
+    for (_, line) in changes {
+        out.push_str(&line);
+        out.push('\n');
+    }
+    out
+}
+
+pub(crate) type ReportEntries = BTreeMap<(String, String), u32>;
+
+fn load_report_entries(path: &Path) -> Result<ReportEntries> {
+    let raw = std::fs::read(path).with_context(|| format!("failed to read {}", path.display()))?;
+    parse_report_entries(&String::from_utf8_lossy(&raw))
+        .with_context(|| format!("invalid size report: {}", path.display()))
+}
+
+pub(crate) fn parse_report_entries(text: &str) -> Result<ReportEntries> {
+    if text.trim_start().starts_with('{') {
+        parse_json_report(text)
+    } else {
+        parse_text_report(text)
+    }
+}
+
+fn parse_text_report(text: &str) -> Result<ReportEntries> {
+    let mut out = ReportEntries::new();
+    for (lineno, line) in text.lines().enumerate() {
+        let line = line.trim_end_matches('\r');
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let mut fields = line.split('\t');
+        let (Some(kind), Some(size), Some(name)) = (fields.next(), fields.next(), fields.next())
+        else {
+            bail!("line {}: expected KIND<TAB>SIZE<TAB>NAME", lineno + 1);
+        };
+        let size = u32::from_str_radix(size, 16)
+            .with_context(|| format!("line {}: invalid size '{size}'", lineno + 1))?;
+        out.insert((kind.to_string(), name.to_string()), size);
+    }
+    Ok(out)
+}
+
+fn parse_json_report(text: &str) -> Result<ReportEntries> {
+    let root = serde_json::from_str::<Value>(text)?;
+    let Some(fields) = root.as_object() else {
+        bail!("top-level value must be an object");
+    };
+    let mut out = ReportEntries::new();
+    for (key, kind) in [
+        ("sections", "section"),
+        ("objects", "object"),
+        ("archives", "archive"),
+        ("symbols", "symbol"),
+    ] {
+        let Some(items) = fields.get(key).and_then(Value::as_array) else {
+            continue;
+        };
+        for item in items {
+            let name = item.get("name").and_then(Value::as_str);
+            let size = item
+                .get("size")
+                .and_then(Value::as_u64)
+                .and_then(|n| u32::try_from(n).ok());
+            let (Some(name), Some(size)) = (name, size) else {
+                bail!("{key}: entries need \"name\" and \"size\"");
+            };
+            out.insert((kind.to_string(), name.to_string()), size);
+        }
+    }
+    Ok(out)
+}
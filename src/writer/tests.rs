@@ -1,14 +1,27 @@
     use std::collections::{BTreeMap, HashMap};
 
+    use super::cpu::check_cpu_rules;
+    use super::displacement::check_byte_displacements;
+    use super::islands::{check_word_displacements, insert_branch_islands};
+    use super::scd::validate_scd;
+    use super::sidecar::build_debug_sidecar;
+    use super::budget::{check_size_budgets, render_memory_usage, SizeBudget};
+    use super::device::DeviceHeaderError;
     use super::expr::{classify_expression_errors, evaluate_a0};
+    use super::prg::{build_prg_image, encode_tos_fixups};
+    use super::selfreloc::{encode_relocation_deltas, prepend_relocation_stub};
+    use super::zfile::build_z_image;
     use super::ExprEntry;
 
     use crate::format::obj::{Command, ObjectFile};
     use crate::layout::plan_layout;
     use crate::resolver::{ObjectSummary, SectionKind, Symbol, resolve_object};
+    use crate::target::Cpu;
     use crate::writer::{
-        MapSizes, apply_x_header_options, build_map_text, build_r_payload, build_x_image,
-        build_x_image_with_options, validate_link_inputs,
+        MapSizes, OutputFormat, OutputOptions, BssPolicy, RelocationCheck, SymbolFilter, SymbolSelection,
+        SymbolTablePolicy,
+        apply_x_header_options, build_map_text, build_r_payload, build_x_image,
+        build_device_image, build_fixed_image, build_x_image_with_options, validate_link_inputs,
         patch_section_size_info,
         validate_r_convertibility,
     };
@@ -100,6 +113,36 @@
         assert_eq!(&with_bss[2..], &[0, 0, 0, 0]);
     }
 
+    #[test]
+    fn r_payload_from_a_linked_image_matches_the_plain_payload() {
+        let obj = text_object(
+            6,
+            vec![
+                Command::Opaque {
+                    code: 0x4201,
+                    payload: vec![0, 0, 0, 2],
+                },
+                Command::RawData(vec![0x4e, 0x75]),
+            ],
+        );
+        let mut sum = mk_summary(2, 6, 0);
+        sum.declared_section_sizes.insert(SectionKind::Bss, 4);
+        let layout = plan_layout(std::slice::from_ref(&sum));
+        let linked = super::link_image(std::slice::from_ref(&obj), std::slice::from_ref(&sum), &layout, false)
+            .expect("link");
+        for omit_bss in [false, true] {
+            let plain = build_r_payload(
+                std::slice::from_ref(&obj),
+                std::slice::from_ref(&sum),
+                &layout,
+                omit_bss,
+                false,
+            )
+            .expect("payload");
+            assert_eq!(super::r_payload_from_linked(&linked, &layout, omit_bss), plain);
+        }
+    }
+
     #[test]
     fn builds_minimal_x_image() {
         let obj = ObjectFile {
@@ -193,11 +236,12 @@
             std::slice::from_ref(&obj),
             std::slice::from_ref(&sum),
             &layout,
-            true,
+            SymbolSelection::all(),
             false,
         )
         .expect("x image");
-        let without_symbols = build_x_image_with_options(&[obj], &[sum], &layout, false, false).expect("x image");
+        let without_symbols =
+            build_x_image_with_options(&[obj], &[sum], &layout, SymbolSelection::new(SymbolTablePolicy::Cut, &SymbolFilter::default()), false).expect("x image");
 
         let with_sym_size =
             u32::from_be_bytes([with_symbols[28], with_symbols[29], with_symbols[30], with_symbols[31]]);
@@ -211,6 +255,317 @@
         assert_eq!(without_sym_size, 0);
     }
 
+    #[test]
+    fn keep_and_strip_symbol_lists_adjust_the_table() {
+        let obj = text_object(2, vec![Command::RawData(vec![0x4e, 0x75])]);
+        let mut sum = mk_summary(2, 2, 0);
+        for name in ["_a", "_b", "_c"] {
+            sum.symbols.push(Symbol {
+                name: name.as_bytes().to_vec(),
+                section: SectionKind::Text,
+                value: 0,
+            });
+        }
+        let layout = plan_layout(std::slice::from_ref(&sum));
+        let names_in = |policy, keep: &[&str], strip: &[&str]| {
+            let filter = SymbolFilter {
+                keep: keep.iter().map(|n| n.as_bytes().to_vec()).collect(),
+                strip: strip.iter().map(|n| n.as_bytes().to_vec()).collect(),
+            };
+            let image = build_x_image_with_options(
+                std::slice::from_ref(&obj),
+                std::slice::from_ref(&sum),
+                &layout,
+                SymbolSelection::new(policy, &filter),
+                false,
+            )
+            .expect("x image");
+            let sym_size = u32::from_be_bytes([image[28], image[29], image[30], image[31]]) as usize;
+            let mut table = &image[64 + 2..64 + 2 + sym_size];
+            let mut names = Vec::new();
+            while !table.is_empty() {
+                let len = table[6..].iter().position(|&b| b == 0).expect("nul");
+                names.push(String::from_utf8_lossy(&table[6..6 + len]).into_owned());
+                table = &table[(6 + len + 2) & !1..];
+            }
+            names
+        };
+        assert_eq!(names_in(SymbolTablePolicy::Keep, &[], &["_b"]), ["_a", "_c"]);
+        assert_eq!(names_in(SymbolTablePolicy::Cut, &["_c"], &[]), ["_c"]);
+        assert_eq!(names_in(SymbolTablePolicy::Cut, &["_b"], &["_b"]), ["_b"]);
+        assert!(names_in(SymbolTablePolicy::Cut, &[], &["_a"]).is_empty());
+    }
+
+    #[test]
+    fn debug_sidecar_carries_symbols_and_scd_at_the_load_address() {
+        let mut obj = text_object(2, vec![Command::RawData(vec![0x4e, 0x75])]);
+        // linfo=(loc=2, line=7)
+        obj.scd_tail = vec![0, 0, 0, 6, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 2, 0, 7];
+        let mut sum = mk_summary(2, 2, 0);
+        sum.symbols.push(Symbol {
+            name: b"_main".to_vec(),
+            section: SectionKind::Text,
+            value: 0,
+        });
+        let layout = plan_layout(std::slice::from_ref(&sum));
+        let options = OutputOptions {
+            format: OutputFormat::R,
+            relocation_check: RelocationCheck::SelfRelocate,
+            bss_policy: BssPolicy::Include,
+            symbol_table: SymbolTablePolicy::Keep,
+            symbol_filter: SymbolFilter::default(),
+            base_address: 0x0001_0000,
+            load_mode: 0,
+            section_info: false,
+            g2lk_mode: false,
+            debug_file: Some("a.dbg".to_string()),
+        };
+        let linked = super::link_image(std::slice::from_ref(&obj), std::slice::from_ref(&sum), &layout, false)
+            .expect("link");
+        let image = build_debug_sidecar(&options, &linked, &[obj], &[sum], &layout, 0x20).expect("sidecar");
+
+        assert_eq!(&image[0..2], b"HU");
+        assert_eq!(&image[4..8], &0x0001_0020u32.to_be_bytes());
+        let sym_size = u32::from_be_bytes([image[28], image[29], image[30], image[31]]) as usize;
+        assert_eq!(&image[64 + 2 + 6..64 + 2 + 11], b"_main");
+        assert_eq!(&image[32..36], &6u32.to_be_bytes());
+        let line_pos = 64 + 2 + sym_size;
+        assert_eq!(&image[line_pos..line_pos + 6], &[0, 0, 0, 2, 0, 7]);
+    }
+
+    #[test]
+    fn size_budgets_name_biggest_contributors() {
+        let sums = [mk_summary(2, 0x10, 2), mk_summary(2, 0x30, 0), mk_summary(2, 0x20, 0)];
+        let layout = plan_layout(&sums);
+        let names = ["a.o".to_string(), "b.o".to_string(), "c.o".to_string()];
+        let text = |limit| SizeBudget {
+            section: Some(SectionKind::Text),
+            limit,
+        };
+        check_size_budgets(&[text(0x60)], &sums, &layout, &names).expect("within budget");
+
+        let err = check_size_budgets(&[text(0x40)], &sums, &layout, &names).expect_err("over budget");
+        assert_eq!(
+            err.to_string(),
+            "text size 0x60 exceeds --max-size limit 0x40 by 0x20; biggest contributors: b.o (0x30), c.o (0x20), a.o (0x10)"
+        );
+        let total = SizeBudget {
+            section: None,
+            limit: 0x61,
+        };
+        assert!(check_size_budgets(&[total], &sums, &layout, &names)
+            .expect_err("total")
+            .to_string()
+            .starts_with("total size 0x62 exceeds"));
+
+        assert_eq!(
+            render_memory_usage(&[text(0x80)], &layout),
+            concat!(
+                "section           used       limit    %used\n",
+                "text        0x00000060  0x00000080    75.0%\n",
+                "data        0x00000002           -        -\n",
+                "total       0x00000062           -        -\n",
+            )
+        );
+    }
+
+    fn text_object(text_size: u32, commands: Vec<Command>) -> ObjectFile {
+        let mut all = vec![
+            Command::Header {
+                section: 0x01,
+                size: text_size,
+                name: b"text".to_vec(),
+            },
+            Command::ChangeSection { section: 0x01 },
+        ];
+        all.extend(commands);
+        all.push(Command::End);
+        ObjectFile {
+            commands: all,
+            scd_tail: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn cpu_rules_reject_odd_words_and_long_displacements_on_68000() {
+        let pad = text_object(2, vec![Command::RawData(vec![0x4e, 0x71])]);
+        let disp = || Command::Opaque {
+            code: 0x6a01,
+            payload: vec![0x00, 0x00, 0x00, 0x00],
+        };
+        let obj = text_object(
+            16,
+            vec![
+                Command::RawData(vec![0x00]),
+                Command::Opaque {
+                    code: 0x4001,
+                    payload: vec![0x00, 0x00, 0x00, 0x00],
+                },
+                Command::RawData(vec![0x00]),
+                // lea (0,a0,d0.w),a1 with a long base displacement
+                Command::RawData(vec![0x43, 0xf0, 0x01, 0x70]),
+                disp(),
+                // dc.l label-* is plain long data
+                Command::RawData(vec![0x4e, 0x71]),
+                disp(),
+            ],
+        );
+        let objects = [pad, obj];
+        let sums = objects.iter().map(resolve_object).collect::<Vec<_>>();
+        let layout = plan_layout(&sums);
+        let names = ["pad.o".to_string(), "main.o".to_string()];
+
+        let err = check_cpu_rules(Cpu::M68000, &objects, &names, &layout).expect_err("68000");
+        assert_eq!(
+            err.to_string(),
+            concat!(
+                "2-byte write at odd address text 0x3 traps on the 68000 in main.o\n",
+                "32-bit displacement at text 0xa needs a 68020 or later (--cpu) in main.o"
+            )
+        );
+        let warnings = check_cpu_rules(Cpu::M68020, &objects, &names, &layout).expect("68020");
+        assert_eq!(
+            warnings,
+            ["text of main.o starts at 0x2; its longs are misaligned on the 68020 (try -e 4)"]
+        );
+    }
+
+    #[test]
+    fn branch_islands_redirect_out_of_range_bsr() {
+        let caller = ObjectFile {
+            commands: vec![
+                Command::Header {
+                    section: 0x01,
+                    size: 4,
+                    name: b"text".to_vec(),
+                },
+                Command::DefineSymbol {
+                    section: 0xff,
+                    value: 1,
+                    name: b"_far".to_vec(),
+                },
+                Command::ChangeSection { section: 0x01 },
+                Command::RawData(vec![0x61, 0x00]), // bsr.w _far
+                Command::Opaque {
+                    code: 0x6501,
+                    payload: vec![0x00, 0x00, 0x00, 0x02, 0x00, 0x01],
+                },
+                Command::End,
+            ],
+            scd_tail: Vec::new(),
+        };
+        let filler = text_object(0x9000, vec![Command::DefineSpace { size: 0x9000 }]);
+        let far = ObjectFile {
+            commands: vec![
+                Command::Header {
+                    section: 0x01,
+                    size: 2,
+                    name: b"text".to_vec(),
+                },
+                Command::DefineSymbol {
+                    section: 0x01,
+                    value: 0,
+                    name: b"_far".to_vec(),
+                },
+                Command::ChangeSection { section: 0x01 },
+                Command::RawData(vec![0x4e, 0x75]),
+                Command::End,
+            ],
+            scd_tail: Vec::new(),
+        };
+        let mut objects = vec![caller, filler, far];
+        let mut sums = objects.iter().map(resolve_object).collect::<Vec<_>>();
+        let names = ["main.o".to_string(), "big.o".to_string(), "far.o".to_string()];
+        let layout = plan_layout(&sums);
+        let err = check_word_displacements(&objects, &names, &sums, &layout).expect_err("out of range");
+        assert_eq!(
+            err.to_string(),
+            "ワードサイズ(-$8000〜$7fff)で表現できない値 in main.o\n at 00000002 (text)"
+        );
+
+        assert_eq!(insert_branch_islands(&mut objects, &mut sums).expect("islands"), 1);
+        let layout = plan_layout(&sums);
+        check_word_displacements(&objects, &names, &sums, &layout).expect("in range");
+        let image = build_x_image(&objects, &sums, &layout).expect("x image");
+        // bsr.w to the stub at 4, then jmp abs.l _far (0xa + 0x9000).
+        assert_eq!(&image[64..74], &[0x61, 0x00, 0x00, 0x02, 0x4e, 0xf9, 0x00, 0x00, 0x90, 0x0a]);
+        let reloc_pos = 64 + 0x900c;
+        assert_eq!(&image[reloc_pos..reloc_pos + 2], &[0x00, 0x06]);
+    }
+
+    fn relative_section_object(rbss_size: u32, rbss_symbol: u32) -> ObjectFile {
+        ObjectFile {
+            commands: vec![
+                Command::Header {
+                    section: 0x01,
+                    size: 4,
+                    name: b"text".to_vec(),
+                },
+                Command::Header {
+                    section: 0x05,
+                    size: 2,
+                    name: b"rdata".to_vec(),
+                },
+                Command::Header {
+                    section: 0x06,
+                    size: rbss_size,
+                    name: b"rbss".to_vec(),
+                },
+                Command::ChangeSection { section: 0x01 },
+                Command::Opaque {
+                    code: 0x4206,
+                    payload: vec![0x00, 0x00, 0x00, 0x02],
+                },
+                Command::ChangeSection { section: 0x05 },
+                Command::RawData(vec![0x12, 0x34]),
+                Command::DefineSymbol {
+                    section: 0x05,
+                    value: 0,
+                    name: b"_rd".to_vec(),
+                },
+                Command::DefineSymbol {
+                    section: 0x06,
+                    value: rbss_symbol,
+                    name: b"_rb".to_vec(),
+                },
+                Command::End,
+            ],
+            scd_tail: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn places_relative_sections_from_the_base_register_in_x_image() {
+        let obj = relative_section_object(4, 2);
+        let sum = resolve_object(&obj);
+        let layout = plan_layout(std::slice::from_ref(&sum));
+        let image = build_x_image_with_options(&[obj], &[sum], &layout, SymbolSelection::all(), false).expect("x image");
+
+        // rbss follows rdata(2) in the relative area, so rbss+2 is base+4 and not relocated.
+        assert_eq!(&image[64..68], &[0x00, 0x00, 0x00, 0x04]);
+        assert_eq!(&image[16..20], &[0x00, 0x00, 0x00, 0x02]);
+        assert_eq!(&image[24..28], &[0x00, 0x00, 0x00, 0x00]);
+        assert_eq!(&image[68..70], &[0x12, 0x34]);
+        assert_eq!(
+            &image[70..],
+            &[
+                0x02, 0x05, 0x00, 0x00, 0x00, 0x00, b'_', b'r', b'd', 0x00, //
+                0x02, 0x06, 0x00, 0x00, 0x00, 0x04, b'_', b'r', b'b', 0x00,
+            ]
+        );
+    }
+
+    #[test]
+    fn rejects_rbss_symbol_beyond_short_displacement_range() {
+        let obj = relative_section_object(0x8004, 0x7ffe);
+        let sum = resolve_object(&obj);
+        let layout = plan_layout(std::slice::from_ref(&sum));
+        let err = build_x_image_with_options(&[obj], &[sum], &layout, SymbolSelection::new(SymbolTablePolicy::Cut, &SymbolFilter::default()), false).expect_err("must fail");
+        assert!(err
+            .to_string()
+            .contains("rbss symbol _rb is out of 16-bit base-relative range: offset $8000"));
+    }
+
     #[test]
     fn applies_x_header_options() {
         let mut payload = vec![0u8; 64];
@@ -300,6 +655,311 @@
         assert!(text.contains("align\t\t\t : 00000002"));
     }
 
+    #[test]
+    fn fixed_image_resolves_relocations_against_base() {
+        let obj = ObjectFile {
+            commands: vec![
+                Command::Header {
+                    section: 0x01,
+                    size: 8,
+                    name: b"text".to_vec(),
+                },
+                Command::ChangeSection { section: 0x01 },
+                Command::Opaque {
+                    code: 0x4201, // dc.l text:adr
+                    payload: vec![0, 0, 0, 4],
+                },
+                Command::RawData(vec![0x4e, 0x75, 0x4e, 0x71]),
+                Command::End,
+            ],
+            scd_tail: Vec::new(),
+        };
+        let sum = mk_summary(2, 8, 0);
+        let layout = plan_layout(std::slice::from_ref(&sum));
+        let (image, exec) =
+            build_fixed_image(&[obj], &[sum], &layout, 0x0001_0000, false).expect("fixed image");
+        assert_eq!(image, vec![0x00, 0x01, 0x00, 0x04, 0x4e, 0x75, 0x4e, 0x71]);
+        assert_eq!(exec, 0);
+    }
+
+    #[test]
+    fn device_image_validates_header_at_text_start() {
+        let driver = |strategy: Command, name: &[u8], start: Option<(u16, u32)>| {
+            let obj = ObjectFile {
+                commands: vec![
+                    Command::Header {
+                        section: 0x01,
+                        size: 26,
+                        name: b"text".to_vec(),
+                    },
+                    Command::ChangeSection { section: 0x01 },
+                    Command::RawData(vec![0xff, 0xff, 0xff, 0xff, 0x80, 0x00]),
+                    strategy,
+                    Command::Opaque {
+                        code: 0x4201, // interrupt: dc.l text+$18
+                        payload: vec![0, 0, 0, 0x18],
+                    },
+                    Command::RawData(name.to_vec()),
+                    Command::RawData(vec![0x4e, 0x75, 0x4e, 0x75]),
+                    Command::End,
+                ],
+                scd_tail: Vec::new(),
+            };
+            let mut sum = mk_summary(2, 26, 0);
+            sum.start_address = start;
+            let layout = plan_layout(std::slice::from_ref(&sum));
+            build_device_image(&[obj], &[sum], &layout, SymbolSelection::new(SymbolTablePolicy::Cut, &SymbolFilter::default()), false)
+        };
+        let strategy = || Command::Opaque {
+            code: 0x4201, // strategy: dc.l text+$16
+            payload: vec![0, 0, 0, 0x16],
+        };
+
+        let image = driver(strategy(), b"NUL     ", None).expect("device image");
+        assert_eq!(&image[0..2], b"HU");
+        let reloc_size = u32::from_be_bytes([image[24], image[25], image[26], image[27]]);
+        assert_eq!(reloc_size, 4);
+
+        let err = driver(Command::RawData(vec![0, 0, 0, 0x16]), b"NUL     ", None)
+            .expect_err("strategy not relocated");
+        assert_eq!(
+            err.downcast_ref::<DeviceHeaderError>(),
+            Some(&DeviceHeaderError::Entry {
+                offset: 0,
+                field: "strategy",
+                value: 0x16,
+            })
+        );
+        let err = driver(strategy(), b"NUL\0\0\0\0\0", None).expect_err("bad name");
+        assert_eq!(
+            err.downcast_ref::<DeviceHeaderError>(),
+            Some(&DeviceHeaderError::CharacterName { offset: 0 })
+        );
+        let err = driver(strategy(), b"NUL     ", Some((0x01, 0x16))).expect_err("bad start");
+        assert_eq!(
+            err.downcast_ref::<DeviceHeaderError>(),
+            Some(&DeviceHeaderError::StartAddress { exec: 0x16 })
+        );
+    }
+
+    #[test]
+    fn z_image_has_601a_header_and_rebased_body() {
+        let obj = |tail: Vec<Command>| {
+            let mut commands = vec![
+                Command::Header {
+                    section: 0x01,
+                    size: 8,
+                    name: b"text".to_vec(),
+                },
+                Command::ChangeSection { section: 0x01 },
+                Command::Opaque {
+                    code: 0x4201, // dc.l text:adr
+                    payload: vec![0, 0, 0, 4],
+                },
+            ];
+            commands.extend(tail);
+            commands.push(Command::End);
+            ObjectFile {
+                commands,
+                scd_tail: Vec::new(),
+            }
+        };
+        let options = |base_address| OutputOptions {
+            format: OutputFormat::Z,
+            relocation_check: RelocationCheck::Strict,
+            bss_policy: BssPolicy::Include,
+            symbol_table: SymbolTablePolicy::Keep,
+            symbol_filter: SymbolFilter::default(),
+            base_address,
+            load_mode: 0,
+            section_info: false,
+            g2lk_mode: false,
+            debug_file: None,
+        };
+        let sum = mk_summary(2, 8, 0);
+        let layout = plan_layout(std::slice::from_ref(&sum));
+        let names = vec!["main.o".to_string()];
+
+        let ok = obj(vec![Command::RawData(vec![0x4e, 0x75, 0x4e, 0x71])]);
+        let image = build_z_image(
+            "a.z",
+            &options(0x0002_0000),
+            std::slice::from_ref(&ok),
+            &names,
+            std::slice::from_ref(&sum),
+            &layout,
+        )
+        .expect("z image");
+        assert_eq!(image.len(), 28 + 8);
+        assert_eq!(&image[0..2], &[0x60, 0x1a]);
+        assert_eq!(&image[2..6], &8u32.to_be_bytes());
+        assert_eq!(&image[6..10], &0u32.to_be_bytes());
+        assert_eq!(&image[22..26], &0x0002_0000u32.to_be_bytes());
+        assert_eq!(&image[26..28], &[0xff, 0xff]);
+        assert_eq!(&image[28..32], &[0x00, 0x02, 0x00, 0x04]);
+
+        let err = build_z_image("a.z", &options(0), &[ok], &names, std::slice::from_ref(&sum), &layout)
+            .expect_err("base required");
+        assert!(err.to_string().contains("ベースアドレス"));
+
+        let computed = obj(vec![
+            Command::Opaque {
+                code: 0x8001, // push text:adr
+                payload: vec![0, 0, 0, 2],
+            },
+            Command::Opaque {
+                code: 0x9a00, // dc.l (sp)
+                payload: Vec::new(),
+            },
+        ]);
+        // The stack-computed address is patched with the load address like any other.
+        let image = build_z_image(
+            "a.z",
+            &options(0x0002_0000),
+            &[computed],
+            &names,
+            std::slice::from_ref(&sum),
+            &layout,
+        )
+        .expect("computed address");
+        assert_eq!(&image[32..36], &[0x00, 0x02, 0x00, 0x02]);
+
+        let ctor = obj(vec![Command::Opaque {
+            code: 0x4c01, // .ctor entry
+            payload: vec![0, 0, 0, 2],
+        }]);
+        let err = build_z_image("a.z", &options(0x0002_0000), &[ctor], &names, &[sum], &layout)
+            .expect_err("ctor entry");
+        assert_eq!(
+            err.to_string(),
+            "Z-format output cannot relocate a constructor table entry in main.o\n at 00000004 (text)"
+        );
+    }
+
+    #[test]
+    fn encodes_tos_fixups_with_254_byte_skips() {
+        assert_eq!(encode_tos_fixups(&[]), vec![0, 0, 0, 0]);
+        assert_eq!(
+            encode_tos_fixups(&[2, 6, 6 + 254, 6 + 254 + 300]),
+            vec![0, 0, 0, 2, 4, 254, 1, 46, 0]
+        );
+    }
+
+    #[test]
+    fn prg_image_has_header_dri_symbols_and_fixups() {
+        let obj = ObjectFile {
+            commands: vec![
+                Command::Header {
+                    section: 0x01,
+                    size: 6,
+                    name: b"text".to_vec(),
+                },
+                Command::Header {
+                    section: 0x02,
+                    size: 4,
+                    name: b"data".to_vec(),
+                },
+                Command::ChangeSection { section: 0x01 },
+                Command::RawData(vec![0x4e, 0x71]),
+                Command::Opaque {
+                    code: 0x4202, // dc.l data:adr
+                    payload: vec![0, 0, 0, 6],
+                },
+                Command::ChangeSection { section: 0x02 },
+                Command::RawData(vec![0, 0, 0, 1]),
+                Command::End,
+            ],
+            scd_tail: Vec::new(),
+        };
+        let mut sum = mk_summary(2, 6, 4);
+        sum.symbols = vec![
+            Symbol {
+                name: b"_main".to_vec(),
+                section: SectionKind::Text,
+                value: 0,
+            },
+            Symbol {
+                name: b"_counter_value".to_vec(),
+                section: SectionKind::Data,
+                value: 0,
+            },
+        ];
+        let layout = plan_layout(std::slice::from_ref(&sum));
+        let options = OutputOptions {
+            format: OutputFormat::Prg,
+            relocation_check: RelocationCheck::Strict,
+            bss_policy: BssPolicy::Include,
+            symbol_table: SymbolTablePolicy::Keep,
+            symbol_filter: SymbolFilter::default(),
+            base_address: 0,
+            load_mode: 0,
+            section_info: false,
+            g2lk_mode: false,
+            debug_file: None,
+        };
+        let image = build_prg_image("a.prg", &options, &[obj], &[sum], &layout).expect("prg");
+
+        assert_eq!(&image[0..2], &[0x60, 0x1a]);
+        assert_eq!(&image[2..6], &6u32.to_be_bytes());
+        assert_eq!(&image[6..10], &4u32.to_be_bytes());
+        assert_eq!(&image[14..18], &(14u32 * 3).to_be_bytes());
+        assert_eq!(&image[28..38], &[0x4e, 0x71, 0, 0, 0, 6, 0, 0, 0, 1]);
+        let sym = &image[38..38 + 42];
+        assert_eq!(&sym[0..14], b"_main\0\0\0\xa2\x00\0\0\0\0");
+        assert_eq!(&sym[14..28], b"_counter\xa4\x48\0\0\0\x06");
+        assert_eq!(&sym[28..42], b"_value\0\0\0\0\0\0\0\0");
+        assert_eq!(&image[38 + 42..], &[0, 0, 0, 2, 0]);
+
+        let first = text_object(
+            6,
+            vec![
+                Command::Opaque {
+                    code: 0x4201, // dc.l text:adr
+                    payload: vec![0, 0, 0, 4],
+                },
+                Command::RawData(vec![0x4e, 0x75]),
+            ],
+        );
+        let sum = mk_summary(2, 6, 0);
+        let layout = plan_layout(std::slice::from_ref(&sum));
+        let err = build_prg_image("a.prg", &options, &[first], &[sum], &layout).expect_err("fixup at 0");
+        assert!(err
+            .to_string()
+            .starts_with("relocated long at text offset 0 cannot be a TOS fixup"));
+    }
+
+    #[test]
+    fn encodes_self_relocation_deltas_with_long_escape() {
+        assert_eq!(encode_relocation_deltas(&[]), vec![0, 0]);
+        assert_eq!(
+            encode_relocation_deltas(&[0, 4, 0x1_0004]),
+            vec![0, 1, 0, 0, 0, 0, 0, 4, 0, 1, 0, 1, 0, 0, 0, 0]
+        );
+    }
+
+    #[test]
+    fn self_relocating_r_patches_table_length_and_entry() {
+        let program = [0x00, 0x00, 0x00, 0x04, 0x4e, 0x75];
+        let image = prepend_relocation_stub(&program, &[0], 4);
+        let table_at = 90;
+        // lea table(pc),a1 lands right after the stub.
+        assert_eq!(&image[6..10], &[0x43, 0xfa, 0x00, 0x52]);
+        assert_eq!(&image[12..18], &[0xd1, 0xfc, 0x00, 0x00, 0x00, 0x08]);
+        // lea done(pc),a2 points at the flag word in front of the table, and
+        // the second entry branches straight to the jump.
+        assert_eq!(&image[18..22], &[0x45, 0xfa, 0x00, 0x44]);
+        assert_eq!(20 + 0x44, table_at - 2);
+        assert_eq!(&image[24..26], &[0x66, 0x2e]);
+        assert_eq!(26 + 0x2e, 72);
+        // The cache flush is skipped below a 68040.
+        assert_eq!(&image[60..66], &[0x0c, 0x00, 0x00, 0x04, 0x65, 0x06]);
+        assert_eq!(&image[66..72], &[0x72, 0x03, 0x70, 0xac, 0x4e, 0x4f]);
+        assert_eq!(&image[72..78], &[0xd1, 0xfc, 0x00, 0x00, 0x00, 0x04]);
+        assert_eq!(&image[86..90], &[0x4e, 0x75, 0x00, 0x00]);
+        assert_eq!(&image[table_at..table_at + 8], &[0, 1, 0, 0, 0, 0, 0, 0]);
+        assert_eq!(&image[table_at + 8..], &program);
+    }
+
     #[test]
     fn writes_relocation_table_for_long_section_refs() {
         let obj = ObjectFile {
@@ -675,7 +1335,7 @@
         });
         let sum1 = mk_summary(2, 6, 0);
         let layout = plan_layout(&[sum0.clone(), sum1.clone()]);
-        let image = build_x_image_with_options(&[sys.clone(), app], &[sum0, sum1], &layout, false, false)
+        let image = build_x_image_with_options(&[sys.clone(), app], &[sum0, sum1], &layout, SymbolSelection::new(SymbolTablePolicy::Cut, &SymbolFilter::default()), false)
             .expect("x image");
         let data_pos = 64 + 8;
         // ctor table at data+4: -1, entry(text+2), 0
@@ -709,7 +1369,7 @@
         };
         let sum = mk_summary(2, 4, 0);
         let layout = plan_layout(std::slice::from_ref(&sum));
-        let err = build_x_image_with_options(&[obj], &[sum], &layout, false, false).expect_err("must fail");
+        let err = build_x_image_with_options(&[obj], &[sum], &layout, SymbolSelection::new(SymbolTablePolicy::Cut, &SymbolFilter::default()), false).expect_err("must fail");
         assert!(err.to_string().contains("ctor table symbol is missing"));
     }
 
@@ -864,7 +1524,7 @@
         let sum1 = mk_summary(2, 2, 0);
         let layout = plan_layout(&[sum0.clone(), sum1.clone()]);
         let err =
-            build_x_image_with_options(&[obj0, obj1], &[sum0, sum1], &layout, false, false).expect_err("must fail");
+            build_x_image_with_options(&[obj0, obj1], &[sum0, sum1], &layout, SymbolSelection::new(SymbolTablePolicy::Cut, &SymbolFilter::default()), false).expect_err("must fail");
         assert!(err.to_string().contains("ctor/dtor table overflows"));
     }
 
@@ -905,7 +1565,7 @@
             value: 0,
         });
         let layout = plan_layout(std::slice::from_ref(&sum));
-        let err = build_x_image_with_options(&[obj], &[sum], &layout, false, false).expect_err("must fail");
+        let err = build_x_image_with_options(&[obj], &[sum], &layout, SymbolSelection::new(SymbolTablePolicy::Cut, &SymbolFilter::default()), false).expect_err("must fail");
         assert!(err
             .to_string()
             .contains("ctor table symbol must be in text/data"));
@@ -1620,6 +2280,189 @@
         assert_eq!(st.len(), 1);
         assert_eq!(st[0].stat, 2);
         assert_eq!(st[0].value, 123);
+
+        // Unlike .neg. and the other unary operators it accepts an address.
+        let mut st = vec![ExprEntry { stat: 1, value: 4 }];
+        assert!(evaluate_a0(0x02, &mut st).is_empty());
+        assert_eq!((st[0].stat, st[0].value), (1, 4));
+        assert_eq!(evaluate_a0(0x01, &mut st), vec!["不正な式"]);
+        assert_eq!(evaluate_a0(0x02, &mut Vec::new()), vec!["計算用スタックに値がありません"]);
+    }
+
+    #[test]
+    fn classifies_xref_and_displacement_commands_by_target_section() {
+        const WORD: &str = "ワードサイズ(-$8000〜$ffff)で表現できない値";
+        const WORD2: &str = "ワードサイズ(-$8000〜$7fff)で表現できない値";
+        const BYTE: &str = "バイトサイズ(-$80〜$ff)で表現できない値";
+        const ATTR_WORD: &str = "アドレス属性シンボルの値をワードサイズで出力";
+        const ATTR_BYTE: &str = "アドレス属性シンボルの値をバイトサイズで出力";
+        let mut summary = mk_summary(2, 8, 0);
+        summary.xrefs.push(Symbol {
+            name: b"_sym".to_vec(),
+            section: SectionKind::Xref,
+            value: 1,
+        });
+        let label = vec![0x00, 0x01];
+        let offset = |off: i32| [label.clone(), off.to_be_bytes().to_vec()].concat();
+        let disp = vec![0x00, 0x00, 0x00, 0x00, 0x00, 0x01];
+        let cases = [
+            (0x45ff, label.clone(), SectionKind::Abs, 0xffff, Vec::<&str>::new()),
+            (0x45ff, label.clone(), SectionKind::Abs, 0x1_0000, vec![WORD]),
+            (0x45ff, label.clone(), SectionKind::Text, 0, vec![ATTR_WORD]),
+            (0x45ff, label.clone(), SectionKind::RData, 0x7fff, vec![]),
+            (0x45ff, label.clone(), SectionKind::RData, 0x8000, vec![WORD2]),
+            (0x45fe, label.clone(), SectionKind::Abs, 0, vec![ATTR_WORD]),
+            (0x47ff, label.clone(), SectionKind::Abs, 0xff, vec![]),
+            (0x47ff, label.clone(), SectionKind::Abs, 0x100, vec![BYTE]),
+            (0x47ff, label.clone(), SectionKind::Data, 0, vec![ATTR_BYTE]),
+            (0x47fd, label.clone(), SectionKind::Abs, 0, vec![ATTR_BYTE]),
+            (0x55ff, offset(2), SectionKind::Abs, 0xfffd, vec![]),
+            (0x55ff, offset(2), SectionKind::Abs, 0xfffe, vec![WORD]),
+            (0x55ff, offset(-2), SectionKind::Bss, 0, vec![ATTR_WORD]),
+            (0x55ff, offset(-2), SectionKind::RBss, 2, vec![]),
+            (0x57ff, offset(-1), SectionKind::Abs, 0xffff_ff81, vec![]),
+            (0x57ff, offset(-1), SectionKind::Abs, 0xffff_ff80, vec![BYTE]),
+            (0x57ff, offset(1), SectionKind::Stack, 0, vec![ATTR_BYTE]),
+            (0x6501, disp.clone(), SectionKind::Text, 0x10_0000, vec![]),
+            (0x6501, disp.clone(), SectionKind::Abs, 0, vec![ATTR_WORD]),
+            (0x6902, disp.clone(), SectionKind::Data, 0, vec![]),
+            (0x6901, disp.clone(), SectionKind::Abs, 0, vec![ATTR_WORD]),
+            (0x6b01, disp.clone(), SectionKind::Text, 0x1000, vec![]),
+            (0x6b03, disp.clone(), SectionKind::Abs, 0, vec![ATTR_BYTE]),
+        ];
+        for (code, payload, section, value, expected) in cases {
+            let globals = HashMap::from([(
+                b"_sym".to_vec(),
+                Symbol {
+                    name: b"_sym".to_vec(),
+                    section,
+                    value,
+                },
+            )]);
+            let cmd = Command::Opaque { code, payload };
+            let msgs = classify_expression_errors(code, &cmd, &summary, &globals, SectionKind::Text, &mut Vec::new());
+            assert_eq!(msgs, expected, "{code:04x} -> {section:?} {value:#x}");
+        }
+    }
+
+    #[test]
+    fn checks_word_and_byte_displacement_ranges_after_layout() {
+        let far = |code: u16, size: u32| {
+            let caller = text_object(
+                size,
+                vec![Command::Opaque {
+                    code,
+                    payload: vec![0x00, 0x00, 0x00, 0x00, 0x00, 0x01],
+                }],
+            );
+            let mut caller_sum = resolve_object(&caller);
+            caller_sum.xrefs.push(Symbol {
+                name: b"_far".to_vec(),
+                section: SectionKind::Xref,
+                value: 1,
+            });
+            let filler = text_object(0x7e, vec![Command::DefineSpace { size: 0x7e }]);
+            let mut target = text_object(2, vec![Command::RawData(vec![0x4e, 0x75])]);
+            target.commands.insert(
+                0,
+                Command::DefineSymbol {
+                    section: 0x01,
+                    value: 0,
+                    name: b"_far".to_vec(),
+                },
+            );
+            let objects = vec![caller, filler, target.clone()];
+            let sums = vec![caller_sum, resolve_object(&objects[1]), resolve_object(&target)];
+            let layout = plan_layout(&sums);
+            let names = ["a.o".to_string()];
+            check_word_displacements(&objects, &names, &sums, &layout)?;
+            check_byte_displacements(&objects, &names, &sums, &layout)
+        };
+        // _far lands at 0x80: a byte displacement from 0 misses it, a word one does not.
+        let err = far(0x6b01, 2).expect_err("byte out of range");
+        assert_eq!(err.to_string(), "バイトサイズ(-$80〜$7f)で表現できない値 in a.o\n at 00000000 (text)");
+        far(0x6501, 2).expect("word in range");
+        far(0x6901, 2).expect("alias in range");
+    }
+
+    #[test]
+    fn relocates_longs_computed_as_addresses_on_the_calc_stack() {
+        let push = |code: u16, value: i32| Command::Opaque {
+            code,
+            payload: value.to_be_bytes().to_vec(),
+        };
+        let op = |code: u16| Command::Opaque {
+            code,
+            payload: Vec::new(),
+        };
+        let obj = text_object(
+            12,
+            vec![
+                // dc.l label+4
+                push(0x8001, 0),
+                push(0x8000, 4),
+                op(0xa010),
+                op(0x9200),
+                // dc.l label2-label
+                push(0x8001, 8),
+                push(0x8001, 0),
+                op(0xa00f),
+                op(0x9600),
+                // dc.l label
+                push(0x8001, 4),
+                op(0x9a00),
+            ],
+        );
+        let sum = resolve_object(&obj);
+        let layout = plan_layout(std::slice::from_ref(&sum));
+        let image = build_x_image(&[obj], &[sum], &layout).expect("x image");
+        assert_eq!(&image[64..76], &[0, 0, 0, 4, 0, 0, 0, 8, 0, 0, 0, 4]);
+        let reloc_size = u32::from_be_bytes([image[24], image[25], image[26], image[27]]) as usize;
+        assert_eq!(&image[64 + 12..64 + 12 + reloc_size], &[0x00, 0x00, 0x00, 0x08]);
+    }
+
+    #[test]
+    fn validates_scd_headers_line_entries_and_info_references() {
+        let cases: [(&str, Vec<u8>, &[&str]); 6] = [
+            ("well formed", vec![
+                0, 0, 0, 6, 0, 0, 0, 18, 0, 0, 0, 3, //
+                0, 0, 0, 2, 0, 7, //
+                0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 1, 0, 1, 0, 0, 0, 0, //
+                b'_', b'a', 0,
+            ], &[]),
+            ("short header", vec![0, 0, 0, 6], &["SCD header is truncated (4 bytes) in a.o"]),
+            ("sizes past the tail", vec![0, 0, 0, 6, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0], &[
+                "SCD tables need 6 bytes but only 2 follow the header in a.o",
+            ]),
+            ("ragged tables", vec![0, 0, 0, 4, 0, 0, 0, 2, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0], &[
+                "SCD line table size 4 is not a multiple of 6 in a.o",
+                "SCD info table size 2 is not a multiple of 18 in a.o",
+            ]),
+            ("line past text, name past ninfo", vec![
+                0, 0, 0, 6, 0, 0, 0, 18, 0, 0, 0, 2, //
+                0, 0, 0, 8, 0, 7, //
+                0, 0, 0, 0, 0, 0, 0, 4, 0, 0, 0, 1, 0, 1, 0, 0, 0, 0, //
+                b'_', 0,
+            ], &[
+                "SCD line entry #0 points at text 0x8, past the 0x2-byte text in a.o",
+                "SCD sinfo entry #0 names ninfo offset 0x4 outside the 2-byte name table in a.o",
+            ]),
+            ("einfo past sinfo", vec![
+                0, 0, 0, 0, 0, 0, 0, 36, 0, 0, 0, 0, //
+                b'.', b'f', b'i', b'l', 0, 0, 0, 0, 0, 0, 0, 1, 0, 0, 0, 0, 0, 0, //
+                0, 0, 0, 0, 0, 0, 0, 5, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0,
+            ], &["SCD einfo entry #0 refers to sinfo #5 but only 1 exist in a.o"]),
+        ];
+        for (label, scd_tail, expected) in cases {
+            let obj = ObjectFile {
+                commands: vec![Command::End],
+                scd_tail,
+            };
+            let problems = validate_scd(&[obj], &["a.o".to_string()], &[mk_summary(2, 2, 0)]);
+            let messages = problems.iter().map(|p| p.message.as_str()).collect::<Vec<_>>();
+            assert_eq!(messages, expected, "{label}");
+            assert!(problems.iter().all(|p| p.object == 0), "{label}");
+        }
     }
 
     #[test]
@@ -2714,6 +3557,217 @@
         assert_eq!(&image[64..66], &[0x12, 0x34]);
     }
 
+    /// Links `ops` at the start of a 4-byte text with 4 bytes of data and 8 of
+    /// bss, next to an object defining `_text` (text) and `_abs` (absolute
+    /// `$1234`), which `ops` see as xref labels 1 and 2. Returns the long
+    /// written at text 0 and the relocation table.
+    fn link_stack_long(ops: Vec<Command>) -> ([u8; 4], Vec<u8>) {
+        let mut commands = vec![
+            Command::Header {
+                section: 0x01,
+                size: 4,
+                name: b"text".to_vec(),
+            },
+            Command::Header {
+                section: 0x02,
+                size: 4,
+                name: b"data".to_vec(),
+            },
+            Command::Header {
+                section: 0x03,
+                size: 8,
+                name: b"bss".to_vec(),
+            },
+            Command::DefineSymbol {
+                section: 0xff,
+                value: 1,
+                name: b"_text".to_vec(),
+            },
+            Command::DefineSymbol {
+                section: 0xff,
+                value: 2,
+                name: b"_abs".to_vec(),
+            },
+            Command::ChangeSection { section: 0x01 },
+        ];
+        commands.extend(ops);
+        commands.extend([
+            Command::ChangeSection { section: 0x02 },
+            Command::RawData(vec![0; 4]),
+            Command::End,
+        ]);
+        let obj = ObjectFile {
+            commands,
+            scd_tail: Vec::new(),
+        };
+        let mut defs = text_object(2, vec![Command::RawData(vec![0x4e, 0x75])]);
+        defs.commands.splice(
+            0..0,
+            [
+                Command::DefineSymbol {
+                    section: 0x01,
+                    value: 0,
+                    name: b"_text".to_vec(),
+                },
+                Command::DefineSymbol {
+                    section: 0x00,
+                    value: 0x1234,
+                    name: b"_abs".to_vec(),
+                },
+            ],
+        );
+        let sums = vec![resolve_object(&obj), resolve_object(&defs)];
+        let layout = plan_layout(&sums);
+        let image = build_x_image(&[obj, defs], &sums, &layout).expect("x image");
+        let text_size = u32::from_be_bytes([image[12], image[13], image[14], image[15]]) as usize;
+        let data_size = u32::from_be_bytes([image[16], image[17], image[18], image[19]]) as usize;
+        let reloc_size = u32::from_be_bytes([image[24], image[25], image[26], image[27]]) as usize;
+        let relocs = image[64 + text_size + data_size..][..reloc_size].to_vec();
+        ([image[64], image[65], image[66], image[67]], relocs)
+    }
+
+    #[test]
+    fn materializes_stack_longs_like_direct_longs_for_every_section() {
+        // Each `80xx` push written by `92/96/9a` must produce the same long and
+        // relocation as the direct `42xx`/`46xx` command for that operand.
+        let operands = [
+            (0x00, vec![0x00, 0x00, 0x12, 0x34]),
+            (0x01, vec![0x00, 0x00, 0x00, 0x02]),
+            (0x02, vec![0x00, 0x00, 0x00, 0x02]),
+            (0x03, vec![0x00, 0x00, 0x00, 0x06]),
+            (0x04, vec![0x00, 0x00, 0x00, 0x02]),
+            (0x05, vec![0x00, 0x00, 0x00, 0x02]),
+            (0xff, vec![0x00, 0x01]),
+            (0xff, vec![0x00, 0x02]),
+        ];
+        for (lo, payload) in operands {
+            let direct_hi: u16 = if lo == 0xff { 0x4600 } else { 0x4200 };
+            let direct = link_stack_long(vec![Command::Opaque {
+                code: direct_hi | lo,
+                payload: payload.clone(),
+            }]);
+            for write in [0x9200u16, 0x9600, 0x9a00] {
+                let push = Command::Opaque {
+                    code: 0x8000 | lo,
+                    payload: payload.clone(),
+                };
+                let stacked = link_stack_long(vec![push, bare_op(write)]);
+                assert_eq!(stacked, direct, "80{lo:02x} -> {write:04x}");
+            }
+        }
+        // Absolute operands are not relocated, section and text xrefs are.
+        let abs = Command::Opaque {
+            code: 0x8000,
+            payload: vec![0x00, 0x00, 0x12, 0x34],
+        };
+        assert_eq!(link_stack_long(vec![abs, bare_op(0x9a00)]), ([0, 0, 0x12, 0x34], vec![]));
+        assert_eq!(
+            link_stack_long(vec![
+                Command::Opaque {
+                    code: 0x80ff,
+                    payload: vec![0x00, 0x01],
+                },
+                bare_op(0x9a00),
+            ]),
+            ([0, 0, 0, 4], vec![0, 0])
+        );
+    }
+
+    #[test]
+    fn materializes_unary_operators_through_stack_longs() {
+        let push = |value: u32| Command::Opaque {
+            code: 0x8000,
+            payload: value.to_be_bytes().to_vec(),
+        };
+        let cases = [
+            (0xa001, 5, 0xffff_fffb),
+            (0xa002, 5, 5),
+            (0xa003, 0, 0xffff_ffff),
+            (0xa003, 7, 0),
+            (0xa004, 0x1234_5678, 0x56),
+            (0xa005, 0x1234_5678, 0x78),
+            (0xa006, 0x1234_5678, 0x1234),
+            (0xa007, 0x1234_5678, 0x5678),
+        ];
+        for (op, value, want) in cases {
+            let (long, relocs) = link_stack_long(vec![push(value), bare_op(op), bare_op(0x9a00)]);
+            assert_eq!(u32::from_be_bytes(long), want, "{op:04x}");
+            assert!(relocs.is_empty(), "{op:04x}");
+        }
+        // Unary plus keeps the address attribute, so the long is still relocated.
+        let text = Command::Opaque {
+            code: 0x8001,
+            payload: vec![0, 0, 0, 2],
+        };
+        let (long, relocs) = link_stack_long(vec![text, bare_op(0xa002), bare_op(0x9a00)]);
+        assert_eq!(long, [0, 0, 0, 2]);
+        assert_eq!(relocs, [0, 0]);
+    }
+
+    #[test]
+    fn stack_writes_check_range_attribute_and_underflow() {
+        const UNDERFLOW: &str = "計算用スタックに値がありません";
+        const BYTE: &str = "バイトサイズ(-$80〜$ff)で表現できない値";
+        const WORD: &str = "ワードサイズ(-$8000〜$ffff)で表現できない値";
+        const WORD2: &str = "ワードサイズ(-$8000〜$7fff)で表現できない値";
+        const ATTR_BYTE: &str = "アドレス属性シンボルの値をバイトサイズで出力";
+        const ATTR_WORD: &str = "アドレス属性シンボルの値をワードサイズで出力";
+        let constant = |value| Some(ExprEntry { stat: 0, value });
+        let address = Some(ExprEntry { stat: 1, value: 0 });
+        let relative = |value| Some(ExprEntry { stat: 2, value });
+        let unknown = Some(ExprEntry { stat: -1, value: 0x1_0000 });
+        let cases: Vec<(u16, Option<ExprEntry>, SectionKind, Vec<&str>)> = vec![
+            (0x9000, None, SectionKind::Text, vec![UNDERFLOW]),
+            (0x9000, constant(0xff), SectionKind::Text, vec![]),
+            (0x9000, constant(-0x80), SectionKind::Text, vec![]),
+            (0x9000, constant(0x100), SectionKind::Text, vec![BYTE]),
+            (0x9000, address, SectionKind::Text, vec![ATTR_BYTE]),
+            (0x9000, relative(0), SectionKind::Text, vec![ATTR_BYTE]),
+            (0x9000, unknown, SectionKind::Text, vec![]),
+            (0x9300, None, SectionKind::Data, vec![UNDERFLOW]),
+            (0x9300, constant(-0x81), SectionKind::Data, vec![BYTE]),
+            (0x9300, address, SectionKind::Data, vec![ATTR_BYTE]),
+            (0x9100, None, SectionKind::Text, vec![UNDERFLOW]),
+            (0x9100, constant(0xffff), SectionKind::Text, vec![]),
+            (0x9100, constant(0x1_0000), SectionKind::Text, vec![WORD]),
+            (0x9100, address, SectionKind::Text, vec![ATTR_WORD]),
+            (0x9100, relative(0x7fff), SectionKind::Text, vec![]),
+            (0x9100, relative(0x8000), SectionKind::Data, vec![WORD2]),
+            (0x9100, relative(0), SectionKind::RData, vec![ATTR_WORD]),
+            (0x9100, unknown, SectionKind::Text, vec![]),
+            (0x9900, None, SectionKind::Text, vec![UNDERFLOW]),
+            (0x9900, constant(0x7fff), SectionKind::Text, vec![]),
+            (0x9900, constant(0x8000), SectionKind::Text, vec![WORD2]),
+            (0x9900, address, SectionKind::Text, vec![ATTR_WORD]),
+            (0x9900, relative(0x10), SectionKind::Bss, vec![]),
+            (0x9900, relative(-0x8001), SectionKind::Stack, vec![WORD2]),
+            (0x9900, relative(0), SectionKind::RData, vec![ATTR_WORD]),
+            (0x9900, unknown, SectionKind::Text, vec![]),
+        ];
+        let mut cases = cases;
+        for code in [0x9200, 0x9600, 0x9a00] {
+            cases.push((code, None, SectionKind::Text, vec![UNDERFLOW]));
+            for entry in [constant(i32::MIN), address, relative(0x10_0000), unknown] {
+                cases.push((code, entry, SectionKind::Data, vec![]));
+            }
+        }
+        for (code, entry, current, expected) in cases {
+            let mut st = entry.into_iter().collect::<Vec<_>>();
+            let cmd = bare_op(code);
+            let msgs =
+                classify_expression_errors(code, &cmd, &mk_summary(2, 0, 0), &HashMap::new(), current, &mut st);
+            assert_eq!(msgs, expected, "{code:04x} {entry:?} in {current:?}");
+            assert!(st.is_empty(), "{code:04x} pops its operand");
+        }
+    }
+
+    fn bare_op(code: u16) -> Command {
+        Command::Opaque {
+            code,
+            payload: Vec::new(),
+        }
+    }
+
     fn assert_label_displacement_for_lo(code_hi: u8, lo: u8, expected: &[u8]) {
         let obj0 = ObjectFile {
             commands: vec![
@@ -2806,6 +3860,191 @@
         assert_label_displacement_for_lo(0x6a, 0x04, &[0x00, 0x00, 0x00, 0x04]);
     }
 
+    #[test]
+    fn first_definition_wins_in_global_symbol_addrs() {
+        let mut s0 = mk_summary(2, 4, 0);
+        s0.symbols = vec![Symbol {
+            name: b"_dup".to_vec(),
+            section: SectionKind::Text,
+            value: 2,
+        }];
+        let mut s1 = mk_summary(2, 4, 0);
+        s1.symbols = vec![Symbol {
+            name: b"_dup".to_vec(),
+            section: SectionKind::Text,
+            value: 0,
+        }];
+        let layout = plan_layout(&[s0.clone(), s1.clone()]);
+        let addrs = super::build_global_symbol_addrs(&[s0, s1], &layout, 8, 0, 0, 0).expect("addrs");
+        assert_eq!(addrs.get(b"_dup".as_slice()).map(|v| v.addr), Some(2));
+    }
+
+    #[test]
+    fn symbol_address_overflow_is_an_error() {
+        let mut s0 = mk_summary(2, 4, 4);
+        s0.symbols = vec![Symbol {
+            name: b"_far".to_vec(),
+            section: SectionKind::Data,
+            value: 0xffff_fff0,
+        }];
+        let layout = plan_layout(std::slice::from_ref(&s0));
+        let err = super::build_global_symbol_addrs(&[s0], &layout, 0x20, 4, 0, 0).expect_err("must overflow");
+        assert_eq!(err.to_string(), "symbol _far overflows the 32-bit address space");
+    }
+
+    #[test]
+    fn size_report_attributes_objects_archives_and_symbols() {
+        let mut s0 = mk_summary(2, 0x10, 0);
+        s0.symbols = vec![
+            Symbol {
+                name: b"_main".to_vec(),
+                section: SectionKind::Text,
+                value: 0,
+            },
+            Symbol {
+                name: b"_helper".to_vec(),
+                section: SectionKind::Text,
+                value: 0x0c,
+            },
+        ];
+        let mut s1 = mk_summary(2, 0x20, 4);
+        s1.symbols = vec![
+            Symbol {
+                name: b"_puts".to_vec(),
+                section: SectionKind::Text,
+                value: 0,
+            },
+            Symbol {
+                name: b"_buf".to_vec(),
+                section: SectionKind::Data,
+                value: 0,
+            },
+        ];
+        let layout = plan_layout(&[s0.clone(), s1.clone()]);
+        let names = vec!["dir/main.o".to_string(), "lib/libc.a(puts.o)".to_string()];
+        let report = super::size_report::build_size_report(&[s0, s1], &layout, &names).expect("size report");
+
+        assert_eq!(report.objects[0].name, "lib/libc.a(puts.o)");
+        assert_eq!(report.objects[0].total, 0x24);
+        assert_eq!(report.objects[1].name, "dir/main.o");
+        assert_eq!(report.objects[1].total, 0x10);
+        assert_eq!(report.archives, vec![("lib/libc.a".to_string(), 0x24)]);
+        let sizes = report
+            .symbols
+            .iter()
+            .map(|s| (s.name.as_str(), s.addr, s.size))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            sizes,
+            vec![("_puts", 0x10, 0x20), ("_main", 0, 0x0c), ("_buf", 0x30, 4), ("_helper", 0x0c, 4)]
+        );
+    }
+
+    #[test]
+    fn size_report_attributes_base_relative_symbols() {
+        let mut s0 = mk_summary(2, 4, 0);
+        s0.declared_section_sizes.insert(SectionKind::RData, 8);
+        s0.declared_section_sizes.insert(SectionKind::RBss, 6);
+        let sym = |name: &[u8], section, value| Symbol {
+            name: name.to_vec(),
+            section,
+            value,
+        };
+        s0.symbols = vec![
+            sym(b"_table", SectionKind::RData, 0),
+            sym(b"_limit", SectionKind::RData, 6),
+            sym(b"_work", SectionKind::RBss, 2),
+            sym(b"_shared", SectionKind::RCommon, 4),
+        ];
+        let mut s1 = mk_summary(2, 2, 0);
+        s1.declared_section_sizes.insert(SectionKind::RData, 4);
+        s1.symbols = vec![sym(b"_msg", SectionKind::RData, 0)];
+        let layout = plan_layout(&[s0.clone(), s1.clone()]);
+        let names = vec!["a.o".to_string(), "b.o".to_string()];
+        let report = super::size_report::build_size_report(&[s0, s1], &layout, &names).expect("size report");
+
+        let mut sizes = report
+            .symbols
+            .iter()
+            .map(|s| (s.name.as_str(), s.section, s.addr, s.size))
+            .collect::<Vec<_>>();
+        sizes.sort_by_key(|&(_, _, addr, _)| addr);
+        assert_eq!(
+            sizes,
+            vec![
+                ("_table", SectionKind::RData, 0, 6),
+                ("_limit", SectionKind::RData, 6, 2),
+                ("_msg", SectionKind::RData, 8, 4),
+                ("_work", SectionKind::RBss, 0x0e, 4),
+                ("_shared", SectionKind::RCommon, 0x12, 4),
+            ]
+        );
+    }
+
+    #[test]
+    fn size_report_diff_accepts_text_and_json_reports() {
+        let mut s0 = mk_summary(2, 0x10, 0);
+        s0.symbols = vec![Symbol {
+            name: b"_main".to_vec(),
+            section: SectionKind::Text,
+            value: 0,
+        }];
+        let layout = plan_layout(std::slice::from_ref(&s0));
+        let names = vec!["main.o".to_string()];
+        let old = super::size_report::build_size_report(std::slice::from_ref(&s0), &layout, &names).expect("size report");
+        s0.declared_section_sizes.insert(SectionKind::Text, 0x18);
+        let layout = plan_layout(std::slice::from_ref(&s0));
+        let new = super::size_report::build_size_report(std::slice::from_ref(&s0), &layout, &names).expect("size report");
+
+        let old_text = super::size_report::render_text("a.x", &old);
+        let new_json = super::size_report::render_json("a.x", &new);
+        let old_entries = super::size_report::parse_report_entries(&old_text).expect("text");
+        let new_entries = super::size_report::parse_report_entries(&new_json).expect("json");
+        let diff = super::size_report::diff_report_entries(&old_entries, &new_entries);
+        assert_eq!(
+            diff,
+            "object\tmain.o\t00000010 -> 00000018 (+8)\n\
+             section\ttext\t00000010 -> 00000018 (+8)\n\
+             symbol\t_main\t00000010 -> 00000018 (+8)\n"
+        );
+    }
+
+    #[test]
+    fn size_report_diff_keeps_same_named_objects_apart() {
+        let names = vec![
+            "a/util.o".to_string(),
+            "b/util.o".to_string(),
+            "a/libx.a(m.o)".to_string(),
+            "b/libx.a(m.o)".to_string(),
+        ];
+        let mut objs = vec![
+            mk_summary(2, 0x10, 0),
+            mk_summary(2, 0x20, 0),
+            mk_summary(2, 0x30, 0),
+            mk_summary(2, 0x40, 0),
+        ];
+        let layout = plan_layout(&objs);
+        let old = super::size_report::build_size_report(&objs, &layout, &names).expect("size report");
+        objs[1].declared_section_sizes.insert(SectionKind::Text, 0x22);
+        objs[2].declared_section_sizes.insert(SectionKind::Text, 0x34);
+        let layout = plan_layout(&objs);
+        let new = super::size_report::build_size_report(&objs, &layout, &names).expect("size report");
+
+        let old_entries =
+            super::size_report::parse_report_entries(&super::size_report::render_json("a.x", &old)).expect("json");
+        let new_entries =
+            super::size_report::parse_report_entries(&super::size_report::render_text("a.x", &new)).expect("text");
+        assert_eq!(old_entries.len(), new_entries.len());
+        let diff = super::size_report::diff_report_entries(&old_entries, &new_entries);
+        assert_eq!(
+            diff,
+            "section\ttext\t000000a0 -> 000000a6 (+6)\n\
+             archive\ta/libx.a\t00000030 -> 00000034 (+4)\n\
+             object\ta/libx.a(m.o)\t00000030 -> 00000034 (+4)\n\
+             object\tb/util.o\t00000020 -> 00000022 (+2)\n"
+        );
+    }
+
     fn mk_summary(align: u32, text: u32, data: u32) -> ObjectSummary {
         let mut declared = BTreeMap::new();
         if text > 0 {
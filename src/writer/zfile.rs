@@ -0,0 +1,117 @@
+use std::path::Path;
+
+use anyhow::{bail, Result};
+
+use crate::format::obj::{Command, ObjectFile};
+use crate::layout::LayoutPlan;
+use crate::resolver::{ObjectSummary, SectionKind};
+
+use super::map::display_obj_name;
+use super::{
+    bss_common_stack_total, build_fixed_image, opcode, patch_section_size_info, put_u32_be,
+    section_tag, section_total, to_human68k_path, walk_commands, OutputOptions,
+};
+
+const Z_MAGIC: [u8; 2] = [0x60, 0x1a];
+const Z_HEADER_SIZE: usize = 28;
+
+/// A value the fixed-address image would have to rebase at load time.
+struct RuntimeRelocation {
+    object: usize,
+    section: SectionKind,
+    offset: u32,
+    what: &'static str,
+}
+
+/// Builds a Human68k Z-format image: a 28-byte `0x601A` header followed by
+/// text and data already resolved for `options.base_address`.
+///
+/// Header layout: magic, text/data/bss sizes, 8 reserved bytes, load address, `$ffff`.
+pub(super) fn build_z_image(
+    output_path: &str,
+    options: &OutputOptions,
+    objects: &[ObjectFile],
+    input_paths: &[String],
+    summaries: &[ObjectSummary],
+    layout: &LayoutPlan,
+) -> Result<Vec<u8>> {
+    if options.base_address == 0 {
+        bail!(
+            "Z形式の出力にはベースアドレス(-b)が必要です: {}",
+            to_human68k_path(Path::new(output_path))
+        );
+    }
+    if let Some(site) = find_runtime_relocation(objects) {
+        bail!(
+            "Z-format output cannot relocate a {} in {}\n at {:08x} ({})",
+            site.what,
+            display_obj_name(
+                input_paths.get(site.object).map(String::as_str),
+                site.object
+            ),
+            site.offset,
+            section_tag(site.section)
+        );
+    }
+    let (mut body, exec) = build_fixed_image(
+        objects,
+        summaries,
+        layout,
+        options.base_address,
+        options.g2lk_mode,
+    )?;
+    if exec != 0 {
+        bail!(
+            "実行開始アドレスがファイル先頭ではありません: {}",
+            to_human68k_path(Path::new(output_path))
+        );
+    }
+    if options.section_info {
+        patch_section_size_info(&mut body, true, summaries, layout)?;
+    }
+
+    let data_size = u32::try_from(body.len())
+        .unwrap_or(u32::MAX)
+        .saturating_sub(section_total(layout, SectionKind::Text));
+    let mut image = vec![0u8; Z_HEADER_SIZE];
+    image[0..2].copy_from_slice(&Z_MAGIC);
+    put_u32_be(&mut image, 2, section_total(layout, SectionKind::Text));
+    put_u32_be(&mut image, 6, data_size);
+    put_u32_be(&mut image, 10, bss_common_stack_total(layout));
+    put_u32_be(&mut image, 22, options.base_address);
+    image[26..28].copy_from_slice(&[0xff, 0xff]);
+    image.extend_from_slice(&body);
+    Ok(image)
+}
+
+/// Finds the first address the linker leaves for the loader instead of
+/// resolving it: ctor/dtor entries. Stack-computed addresses are patched with
+/// the final placement, so a fixed image already holds their loaded value.
+fn find_runtime_relocation(objects: &[ObjectFile]) -> Option<RuntimeRelocation> {
+    for (idx, obj) in objects.iter().enumerate() {
+        let mut found = None;
+        walk_commands(obj, |cmd, current, local, _calc_stack| {
+            let Command::Opaque { code, .. } = cmd else {
+                return;
+            };
+            if found.is_some() {
+                return;
+            }
+            let what = match *code {
+                opcode::OP_CTOR_ENTRY => "constructor table entry",
+                opcode::OP_DTOR_ENTRY => "destructor table entry",
+                _ => return,
+            };
+            found = Some(RuntimeRelocation {
+                object: idx,
+                section: current,
+                offset: local,
+                what,
+            });
+        });
+        if found.is_some() {
+            return found;
+        }
+    }
+    None
+}